@@ -1,10 +1,12 @@
-use memmap2::Mmap;
+use sqlite3_dump::diff::RowChange;
 use sqlite3_dump::error::SQLiteError;
+use sqlite3_dump::journal::HotJournal;
 use sqlite3_dump::model::LeafTableCell;
-use sqlite3_dump::{model, HashMap, Reader, SqlSchema};
+use sqlite3_dump::recover::FreeSpace;
+use sqlite3_dump::{model, HashMap, Pager, PreadPager, Reader, SqlSchema};
 use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 // this example is an ai slop
 
@@ -12,7 +14,7 @@ use std::path::PathBuf;
 #[derive(argh::FromArgs)]
 /// Export SQLite tables to CSV or list available tables
 struct Args {
-    /// path to SQLite database file
+    /// path to SQLite database file, `-` reads it from stdin
     #[argh(positional)]
     database: PathBuf,
 
@@ -23,22 +25,200 @@ struct Args {
     /// output CSV file path (optional, defaults to stdout)
     #[argh(option, short = 'o')]
     output: Option<PathBuf>,
+
+    /// read with positioned reads instead of mapping the file
+    #[argh(switch)]
+    pread: bool,
+
+    /// write the deleted rows left on free pages instead of a table, each with its page and
+    /// rowid in front
+    #[argh(switch)]
+    carve: bool,
+
+    /// write the deleted records left in the free space of live table pages instead of a
+    /// table, each with its table, page, offset, kind of free space, confidence and rowid
+    /// in front
+    #[argh(switch)]
+    recover: bool,
+
+    /// write every row readable on a table leaf page instead of a table, even with a damaged
+    /// schema or header, each with its table or lost_and_found, the root page of its
+    /// b-tree, its page, the record shape of the page and its rowid in front
+    #[argh(switch)]
+    salvage: bool,
+
+    /// list the commits in the `-wal` file next to the database instead of a table
+    #[argh(switch)]
+    commits: bool,
+
+    /// read the database as it was right after this commit of its `-wal` file, 0 for the
+    /// database file alone (default: the last commit)
+    #[argh(option)]
+    commit: Option<usize>,
+
+    /// write the rows of the table that changed since this commit instead of the table,
+    /// each with `inserted`, `updated` or `deleted` and its rowid in front
+    #[argh(option)]
+    diff_from: Option<usize>,
+
+    /// hot rollback journal next to the database: warn, refuse or rollback (default: warn)
+    #[argh(option, default = "HotJournal::Warn")]
+    hot_journal: HotJournal,
 }
 
 fn main() {
-    let args: Args = argh::from_env();
-    let reader = open_database(&args.database);
+    let args = args_from_env();
+    if args.database.as_os_str() == "-" {
+        if args.commits || args.commit.is_some() || args.diff_from.is_some() {
+            eprintln!(
+                "Error: --commits, --commit and --diff-from read the `-wal` file, a database on stdin has none"
+            );
+            std::process::exit(1);
+        }
+        // always the database alone, the commit options are rejected above
+        run(&args, |_| read_stdin(&args));
+    } else if args.pread {
+        run(&args, |commit| {
+            at_commit(hot_journal(open_pread(&args), &args), commit)
+        });
+    } else {
+        run(&args, |commit| {
+            at_commit(hot_journal(open_database(&args), &args), commit)
+        });
+    }
+}
+
+/// like `argh::from_env`, but a bare `-` for the database is read from stdin
+///
+/// argh takes `-` for an unknown option, so it is passed on as an empty path
+fn args_from_env() -> Args {
+    use argh::FromArgs;
+
+    let strings: Vec<String> = std::env::args().collect();
+    let cmd = Path::new(&strings[0])
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(&strings[0]);
+    let strs: Vec<&str> = strings[1..]
+        .iter()
+        .map(|s| if s == "-" { "" } else { s.as_str() })
+        .collect();
+
+    let mut args = Args::from_args(&[cmd], &strs).unwrap_or_else(|early_exit| {
+        std::process::exit(match early_exit.status {
+            Ok(()) => {
+                println!("{}", early_exit.output);
+                0
+            }
+            Err(()) => {
+                eprintln!(
+                    "{}\nRun {} --help for more information.",
+                    early_exit.output, cmd
+                );
+                1
+            }
+        })
+    });
+    if args.database.as_os_str().is_empty() {
+        args.database = PathBuf::from("-");
+    }
+    args
+}
+
+/// `open` reads the database at a commit of its WAL, `None` for the last
+fn run<P: Pager>(args: &Args, open: impl Fn(Option<usize>) -> Reader<P>) {
+    let reader = &open(args.commit);
+    if args.salvage {
+        return salvage(reader, args.output.as_ref());
+    }
+    if args.carve {
+        return carve_free_pages(reader, args.output.as_ref());
+    }
+    if args.recover {
+        return recover_deleted_records(reader, args.output.as_ref());
+    }
+    if args.commits {
+        return list_commits(reader);
+    }
+
     let tables = reader.get_tables_map().expect("Failed to get tables");
 
-    match args.table {
-        None => list_tables(tables),
-        Some(ref table_name) => dump_table(&reader, tables, table_name, args.output.as_ref()),
+    match (&args.table, args.diff_from) {
+        (None, _) => list_tables(tables),
+        (Some(table_name), Some(commit)) => diff_table(
+            &open(Some(commit)),
+            reader,
+            table_name,
+            args.output.as_ref(),
+        ),
+        (Some(table_name), None) => dump_table(reader, tables, table_name, args.output.as_ref()),
     }
 }
 
-fn open_database(path: &PathBuf) -> Reader<memmap2::Mmap> {
-    Reader::open_mmap(path).unwrap_or_else(|_| {
-        eprintln!("Error: Failed to open database '{}'", path.display());
+/// `--salvage` opens the database without trusting its header
+fn open_database(args: &Args) -> Reader<memmap2::Mmap> {
+    let reader = if args.salvage {
+        Reader::salvage_mmap(&args.database)
+    } else {
+        Reader::open_mmap(&args.database)
+    };
+    reader.unwrap_or_else(|e| open_failed(&args.database, e))
+}
+
+fn open_pread(args: &Args) -> Reader<PreadPager> {
+    let cache_pages = PreadPager::DEFAULT_CACHE_PAGES;
+    let reader = if args.salvage {
+        Reader::salvage_pread(&args.database, cache_pages)
+    } else {
+        Reader::open_pread(&args.database, cache_pages)
+    };
+    reader.unwrap_or_else(|e| open_failed(&args.database, e))
+}
+
+fn open_failed(path: &Path, e: SQLiteError) -> ! {
+    eprintln!("Error: Failed to open database '{}': {}", path.display(), e);
+    std::process::exit(1);
+}
+
+/// deal with a hot journal next to the database as `--hot-journal` says
+fn hot_journal<P: Pager>(reader: Reader<P>, args: &Args) -> Reader<P> {
+    let reader = reader.on_hot_journal(args.hot_journal).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+
+    if let (HotJournal::Warn, Some(journal)) = (args.hot_journal, reader.hot_journal()) {
+        eprintln!(
+            "Warning: hot journal with {} saved pages next to '{}', the database may be half written, \
+             use --hot-journal rollback to read the last commit",
+            journal.record_count(),
+            args.database.display()
+        );
+    }
+    reader
+}
+
+/// move to commit `commit` of the WAL, `None` stays at the last
+fn at_commit<P: Pager>(reader: Reader<P>, commit: Option<usize>) -> Reader<P> {
+    let Some(commit) = commit else {
+        return reader;
+    };
+    reader.at_commit(commit).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    })
+}
+
+/// `--salvage` reads the database without trusting its header
+fn read_stdin(args: &Args) -> Reader<Vec<u8>> {
+    let stdin = std::io::stdin().lock();
+    let reader = if args.salvage {
+        Reader::salvage_from(stdin)
+    } else {
+        Reader::read_from(stdin)
+    };
+    reader.unwrap_or_else(|e| {
+        eprintln!("Error: Failed to read database from stdin: {:?}", e);
         std::process::exit(1);
     })
 }
@@ -50,19 +230,151 @@ fn list_tables(tables: &HashMap<String, Option<SqlSchema>>) {
     }
 }
 
+fn list_commits(reader: &Reader<impl Pager>) {
+    let Some(wal) = reader.wal() else {
+        println!("No WAL next to the database");
+        return;
+    };
+
+    println!("Commits in WAL:");
+    for commit in wal.commits() {
+        let pages: Vec<String> = commit.pages.iter().map(u32::to_string).collect();
+        println!(
+            "  {}: {} frames, {} pages after it, wrote pages {}",
+            commit.number,
+            commit.frames.len(),
+            commit.db_size,
+            pages.join(" ")
+        );
+    }
+}
+
 fn dump_table(
-    reader: &Reader<Mmap>,
+    reader: &Reader<impl Pager>,
     tables: &HashMap<String, Option<SqlSchema>>,
     table_name: &str,
     output_path: Option<&PathBuf>,
 ) {
-    if !tables.contains_key(table_name) {
+    // names are case-insensitive, the map is keyed by the name as it was created
+    let object = reader
+        .schema_object(table_name)
+        .expect("Failed to get tables");
+    let Some((table_name, schema)) = object.and_then(|object| tables.get_key_value(&object.name))
+    else {
         eprintln!("Error: table '{}' not found", table_name);
         std::process::exit(1);
+    };
+
+    let schema = schema.as_ref();
+    let without_rowid = schema.is_some_and(|schema| schema.without_rowid);
+    // a rowid alias already holds the rowid, otherwise it goes in front of the columns
+    let with_rowid = schema.is_none_or(|schema| schema.rowid_alias.is_none());
+
+    let mut output = create_output_writer(output_path);
+    if without_rowid {
+        stream_without_rowid_table_to_csv(reader, table_name, &mut output);
+    } else {
+        stream_table_to_csv(reader, table_name, with_rowid, &mut output);
     }
+    output.flush().expect("Failed to flush output");
+}
+
+fn diff_table(
+    older: &Reader<impl Pager>,
+    newer: &Reader<impl Pager>,
+    table_name: &str,
+    output_path: Option<&PathBuf>,
+) {
+    let mut output = create_output_writer(output_path);
+    older
+        .diff_table(newer, table_name, |change, old_row, new_row| {
+            // the row as it is now, or as it was before it was deleted
+            let Some(row) = new_row.or(old_row) else {
+                return Ok(());
+            };
+            let change: &[u8] = match change {
+                RowChange::Inserted => b"inserted",
+                RowChange::Updated => b"updated",
+                RowChange::Deleted => b"deleted",
+            };
+            let column_values = row.values()?;
+            let mut itoa_buf = itoa::Buffer::new();
+            output
+                .write_all(change)
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| output.write_all(itoa_buf.format(row.rowid).as_bytes()))
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| write_values_to_csv(newer, &column_values, &mut output))
+                .map_err(SQLiteError::IOError)
+        })
+        .expect("Failed to diff table");
+    output.flush().expect("Failed to flush output");
+}
+
+fn salvage(reader: &Reader<impl Pager>, output_path: Option<&PathBuf>) {
+    let mut output = create_output_writer(output_path);
+    reader
+        .salvage(|row, column_values| {
+            let mut itoa_buf = itoa::Buffer::new();
+            write_csv_text(row.table.unwrap_or("lost_and_found"), &mut output)
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| output.write_all(itoa_buf.format(row.root_page).as_bytes()))
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| output.write_all(itoa_buf.format(row.pageno).as_bytes()))
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| output.write_all(itoa_buf.format(row.shape).as_bytes()))
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| output.write_all(itoa_buf.format(row.rowid).as_bytes()))
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| write_values_to_csv(reader, column_values, &mut output))
+                .map_err(SQLiteError::IOError)
+        })
+        .expect("Failed to salvage database");
+    output.flush().expect("Failed to flush output");
+}
 
+fn carve_free_pages(reader: &Reader<impl Pager>, output_path: Option<&PathBuf>) {
     let mut output = create_output_writer(output_path);
-    stream_table_to_csv(reader, table_name, &mut output);
+    reader
+        .carve_free_pages(|pageno, row, column_values| {
+            let mut itoa_buf = itoa::Buffer::new();
+            output
+                .write_all(itoa_buf.format(pageno).as_bytes())
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| write_row_to_csv(reader, row, column_values, true, &mut output))
+                .map_err(SQLiteError::IOError)
+        })
+        .expect("Failed to carve free pages");
+    output.flush().expect("Failed to flush output");
+}
+
+fn recover_deleted_records(reader: &Reader<impl Pager>, output_path: Option<&PathBuf>) {
+    let mut output = create_output_writer(output_path);
+    reader
+        .recover_deleted_records(|record, column_values| {
+            let found_in: &[u8] = match record.found_in {
+                FreeSpace::Freeblock => b"freeblock",
+                FreeSpace::Unallocated => b"unallocated",
+            };
+            let mut itoa_buf = itoa::Buffer::new();
+            write_csv_text(record.table, &mut output)
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| output.write_all(itoa_buf.format(record.pageno).as_bytes()))
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| output.write_all(itoa_buf.format(record.offset).as_bytes()))
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| output.write_all(found_in))
+                .and_then(|_| write!(output, ",{:.2},", record.confidence))
+                .and_then(|_| match record.rowid {
+                    // an unknown rowid is an empty field like NULL
+                    Some(rowid) => output.write_all(itoa_buf.format(rowid).as_bytes()),
+                    None => Ok(()),
+                })
+                .and_then(|_| output.write_all(b","))
+                .and_then(|_| write_values_to_csv(reader, column_values, &mut output))
+                .map_err(SQLiteError::IOError)
+        })
+        .expect("Failed to recover deleted records");
     output.flush().expect("Failed to flush output");
 }
 
@@ -79,43 +391,64 @@ fn create_output_writer(output_path: Option<&PathBuf>) -> BufWriter<Box<dyn Writ
 }
 
 fn stream_table_to_csv(
-    reader: &Reader<Mmap>,
+    reader: &Reader<impl Pager>,
     table_name: &str,
+    with_rowid: bool,
     output: &mut BufWriter<Box<dyn Write>>,
 ) {
     reader
         .stream_table_rows_sequential(table_name, |row, column_values| {
-            write_row_to_csv(reader, row, column_values, output).map_err(SQLiteError::IOError)
+            write_row_to_csv(reader, row, column_values, with_rowid, output)
+                .map_err(SQLiteError::IOError)
         })
         .expect("Failed to stream table");
 }
 
+fn stream_without_rowid_table_to_csv(
+    reader: &Reader<impl Pager>,
+    table_name: &str,
+    output: &mut BufWriter<Box<dyn Write>>,
+) {
+    reader
+        .stream_without_rowid_rows(table_name, |column_values| {
+            write_values_to_csv(reader, column_values, output).map_err(SQLiteError::IOError)
+        })
+        .expect("Failed to stream table");
+}
+
+fn write_values_to_csv(
+    reader: &Reader<impl Pager>,
+    column_values: &[Option<model::Payload>],
+    output: &mut BufWriter<Box<dyn Write>>,
+) -> Result<(), std::io::Error> {
+    for (idx, value) in column_values.iter().enumerate() {
+        if idx > 0 {
+            output.write_all(b",")?;
+        }
+        write_value_to_csv(reader, value, output)?;
+    }
+
+    output.write_all(b"\n")?;
+    Ok(())
+}
+
 fn write_row_to_csv(
-    reader: &Reader<Mmap>,
+    reader: &Reader<impl Pager>,
     row: &LeafTableCell<'_>,
     column_values: &[Option<model::Payload>],
+    with_rowid: bool,
     output: &mut BufWriter<Box<dyn Write>>,
 ) -> Result<(), std::io::Error> {
+    if !with_rowid {
+        return write_values_to_csv(reader, column_values, output);
+    }
+
     let mut itoa_buf = itoa::Buffer::new();
     output.write_all(itoa_buf.format(row.rowid).as_bytes())?;
 
-    let skip_first = column_values.first().is_some_and(|v| v.is_none());
-    let values_to_output = if skip_first {
-        &column_values[1..]
-    } else {
-        column_values
-    };
-
-    let overflow_data =
-        if row.overflow_page_no.is_some() && values_to_output.iter().any(|v| v.is_none()) {
-            reader.reconstruct_full_payload(row).ok()
-        } else {
-            None
-        };
-
-    for value in values_to_output.iter() {
+    for value in column_values.iter() {
         output.write_all(b",")?;
-        write_value_to_csv(reader, value, &overflow_data, output)?;
+        write_value_to_csv(reader, value, output)?;
     }
 
     output.write_all(b"\n")?;
@@ -123,20 +456,14 @@ fn write_row_to_csv(
 }
 
 fn write_value_to_csv(
-    reader: &Reader<Mmap>,
+    reader: &Reader<impl Pager>,
     value: &Option<model::Payload>,
-    overflow_data: &Option<Vec<u8>>,
     output: &mut BufWriter<Box<dyn Write>>,
 ) -> Result<(), std::io::Error> {
     const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
 
     match value {
-        None if overflow_data.is_some() => {
-            for &byte in overflow_data.as_ref().unwrap() {
-                output.write_all(&[HEX_CHARS[(byte >> 4) as usize]])?;
-                output.write_all(&[HEX_CHARS[(byte & 0x0f) as usize]])?;
-            }
-        }
+        // NULL is an empty field, empty text and blobs are a quoted empty string
         None => {}
         Some(model::Payload::I64(v)) => {
             let mut itoa_buf = itoa::Buffer::new();
@@ -150,6 +477,7 @@ fn write_value_to_csv(
             let text = t.decode(reader.header.db_text_encoding);
             write_csv_text(&text, output)?;
         }
+        Some(model::Payload::Blob([])) => output.write_all(b"\"\"")?,
         Some(model::Payload::Blob(b)) => {
             for byte in b.iter() {
                 output.write_all(&[HEX_CHARS[(byte >> 4) as usize]])?;
@@ -166,9 +494,10 @@ fn write_csv_text(
     output: &mut BufWriter<Box<dyn Write>>,
 ) -> Result<(), std::io::Error> {
     let bytes = text.as_bytes();
-    let needs_quoting = bytes
-        .iter()
-        .any(|&b| matches!(b, b',' | b'"' | b'\n' | b'\r'));
+    let needs_quoting = bytes.is_empty()
+        || bytes
+            .iter()
+            .any(|&b| matches!(b, b',' | b'"' | b'\n' | b'\r'));
 
     if needs_quoting {
         output.write_all(b"\"")?;
@@ -1,5 +1,6 @@
-use sqlite3_dump::parquet_writer;
-use sqlite3_dump::{HashMap, Reader, SqlSchema};
+use sqlite3_dump::journal::HotJournal;
+use sqlite3_dump::parquet_writer::{self, MixedTypes};
+use sqlite3_dump::{HashMap, Pager, PreadPager, Reader, SqlSchema};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
@@ -9,7 +10,7 @@ use std::time::Instant;
 #[derive(argh::FromArgs)]
 /// SQLite to Parquet exporter
 struct Args {
-    /// path to SQLite database file
+    /// path to SQLite database file, `-` reads it from stdin
     #[argh(positional)]
     database: PathBuf,
 
@@ -24,13 +25,70 @@ struct Args {
     /// number of rows per batch (default: 10000)
     #[argh(option, short = 'b', default = "10000")]
     batch_size: usize,
+
+    /// values that don't fit their column: coerce, lossless or strict (default: coerce)
+    #[argh(option, short = 'm', default = "MixedTypes::Coerce")]
+    mixed_types: MixedTypes,
+
+    /// read with positioned reads instead of mapping the file
+    #[argh(switch)]
+    pread: bool,
+
+    /// hot rollback journal next to the database: warn, refuse or rollback (default: warn)
+    #[argh(option, default = "HotJournal::Warn")]
+    hot_journal: HotJournal,
 }
 
 fn main() {
-    let args: Args = argh::from_env();
+    let args = args_from_env();
+
+    if args.database.as_os_str() == "-" {
+        run(&args, &read_stdin());
+    } else if args.pread {
+        run(&args, &hot_journal(open_pread(&args.database), &args));
+    } else {
+        run(&args, &hot_journal(open_database(&args.database), &args));
+    }
+}
 
-    let reader = open_database(&args.database);
+/// like `argh::from_env`, but a bare `-` for the database is read from stdin
+///
+/// argh takes `-` for an unknown option, so it is passed on as an empty path
+fn args_from_env() -> Args {
+    use argh::FromArgs;
+
+    let strings: Vec<String> = std::env::args().collect();
+    let cmd = Path::new(&strings[0])
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(&strings[0]);
+    let strs: Vec<&str> = strings[1..]
+        .iter()
+        .map(|s| if s == "-" { "" } else { s.as_str() })
+        .collect();
+
+    let mut args = Args::from_args(&[cmd], &strs).unwrap_or_else(|early_exit| {
+        std::process::exit(match early_exit.status {
+            Ok(()) => {
+                println!("{}", early_exit.output);
+                0
+            }
+            Err(()) => {
+                eprintln!(
+                    "{}\nRun {} --help for more information.",
+                    early_exit.output, cmd
+                );
+                1
+            }
+        })
+    });
+    if args.database.as_os_str().is_empty() {
+        args.database = PathBuf::from("-");
+    }
+    args
+}
 
+fn run(args: &Args, reader: &Reader<impl Pager>) {
     let db_name = get_db_name(&args.database);
 
     if let Some(table_name) = &args.table {
@@ -38,11 +96,23 @@ fn main() {
             .output
             .clone()
             .unwrap_or(format!("{table_name}.parquet"));
-        print_header(&args, &output_path, &reader);
-        export_single_table(&reader, table_name, &output_path, args.batch_size);
+        print_header(args, &output_path, reader);
+        export_single_table(
+            reader,
+            table_name,
+            &output_path,
+            args.batch_size,
+            args.mixed_types,
+        );
     } else {
         let output_dir = prepare_output_dir(&args.output);
-        export_all_tables(&reader, &output_dir, args.batch_size, db_name);
+        export_all_tables(
+            reader,
+            &output_dir,
+            args.batch_size,
+            args.mixed_types,
+            db_name,
+        );
     }
 }
 
@@ -76,7 +146,55 @@ fn open_database(database: &PathBuf) -> Reader<memmap2::Mmap> {
     reader
 }
 
-fn print_header(args: &Args, output_dir: &str, reader: &Reader<impl AsRef<[u8]> + Sync>) {
+fn open_pread(database: &PathBuf) -> Reader<PreadPager> {
+    let start = Instant::now();
+    let reader = match Reader::open_pread(database, PreadPager::DEFAULT_CACHE_PAGES) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error: Failed to open database: {:?}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Database opened in {:?}", start.elapsed());
+    println!();
+    reader
+}
+
+/// deal with a hot journal next to the database as `--hot-journal` says
+fn hot_journal<P: Pager>(reader: Reader<P>, args: &Args) -> Reader<P> {
+    let reader = reader.on_hot_journal(args.hot_journal).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+
+    if let (HotJournal::Warn, Some(journal)) = (args.hot_journal, reader.hot_journal()) {
+        eprintln!(
+            "Warning: hot journal with {} saved pages next to '{}', the database may be half written, \
+             use --hot-journal rollback to read the last commit",
+            journal.record_count(),
+            args.database.display()
+        );
+    }
+    reader
+}
+
+fn read_stdin() -> Reader<Vec<u8>> {
+    let start = Instant::now();
+    let reader = match Reader::read_from(std::io::stdin().lock()) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error: Failed to read database from stdin: {:?}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Database read from stdin in {:?}", start.elapsed());
+    println!();
+    reader
+}
+
+fn print_header(args: &Args, output_dir: &str, reader: &Reader<impl Pager>) {
     println!("SQLite to Parquet Exporter");
     println!("==========================");
     println!("Database: {}", args.database.display());
@@ -84,12 +202,18 @@ fn print_header(args: &Args, output_dir: &str, reader: &Reader<impl AsRef<[u8]>
     println!("Text encoding: {:?}", reader.header.db_text_encoding);
     println!("Output: {}", output_dir);
     println!("Batch size: {}", args.batch_size);
+    println!("Mixed types: {:?}", args.mixed_types);
     println!();
 }
 
 fn get_db_name(database: &Path) -> &str {
+    let database = match database.extension().and_then(|ext| ext.to_str()) {
+        Some("gz" | "zst" | "xz") => Path::new(database.file_stem().unwrap_or_default()),
+        _ => database,
+    };
     database
         .file_stem()
+        .filter(|stem| *stem != "-")
         .and_then(|s| s.to_str())
         .unwrap_or("database")
 }
@@ -112,10 +236,11 @@ fn create_db_dir(output_dir: &str, db_name: &str) -> String {
 }
 
 fn export_single_table(
-    reader: &Reader<impl AsRef<[u8]> + Sync>,
+    reader: &Reader<impl Pager>,
     table_name: &str,
     output_file: &str,
     batch_size: usize,
+    mixed_types: MixedTypes,
 ) {
     println!("Exporting table: {}", table_name);
     println!("Output file: {}", output_file);
@@ -123,7 +248,7 @@ fn export_single_table(
 
     let export_start = Instant::now();
 
-    match export_table(reader, table_name, output_file, batch_size) {
+    match export_table(reader, table_name, output_file, batch_size, mixed_types) {
         Ok(row_count) => {
             print_single_table_summary(table_name, row_count, &export_start, output_file)
         }
@@ -162,9 +287,10 @@ fn print_single_table_summary(
 }
 
 fn export_all_tables(
-    reader: &Reader<impl AsRef<[u8]> + Sync>,
+    reader: &Reader<impl Pager>,
     output_dir: &str,
     batch_size: usize,
+    mixed_types: MixedTypes,
     db_name: &str,
 ) {
     let tables = match reader.get_tables_map() {
@@ -187,14 +313,15 @@ fn export_all_tables(
     println!();
 
     let db_dir = create_db_dir(output_dir, db_name);
-    process_all_tables(reader, tables, &db_dir, batch_size);
+    process_all_tables(reader, tables, &db_dir, batch_size, mixed_types);
 }
 
 fn process_all_tables(
-    reader: &Reader<impl AsRef<[u8]> + Sync>,
+    reader: &Reader<impl Pager>,
     tables: &HashMap<String, Option<SqlSchema>>,
     db_dir: &str,
     batch_size: usize,
+    mixed_types: MixedTypes,
 ) {
     let total_start = Instant::now();
     let mut total_rows = 0;
@@ -204,7 +331,7 @@ fn process_all_tables(
         println!("Exporting table: {}", table_name);
         let output_file = format!("{}/{}.parquet", db_dir, table_name);
 
-        match export_table(reader, table_name, &output_file, batch_size) {
+        match export_table(reader, table_name, &output_file, batch_size, mixed_types) {
             Ok(row_count) => {
                 total_rows += row_count;
                 successful_exports += 1;
@@ -217,18 +344,21 @@ fn process_all_tables(
 }
 
 fn export_table(
-    reader: &Reader<impl AsRef<[u8]> + Sync>,
+    reader: &Reader<impl Pager>,
     table_name: &str,
     output_file: &str,
     batch_size: usize,
+    mixed_types: MixedTypes,
 ) -> sqlite3_dump::error::Result<usize> {
     let export_start = Instant::now();
-    let result = parquet_writer::export_table(reader, table_name, output_file, batch_size);
-    if let Ok(row_count) = &result {
+    let result =
+        parquet_writer::export_table(reader, table_name, output_file, batch_size, mixed_types);
+    if let Ok(exported) = &result {
+        let row_count = exported.rows;
         let duration = export_start.elapsed();
 
         let rows_per_sec = if duration.as_secs() > 0 {
-            *row_count as f64 / duration.as_secs_f64()
+            row_count as f64 / duration.as_secs_f64()
         } else {
             0.0
         };
@@ -241,8 +371,14 @@ fn export_table(
             "  ✓ {}: {} rows ({:.2} MB) - {:.2?} ({:.0} rows/sec)",
             table_name, row_count, size_mb, duration, rows_per_sec
         );
+        if exported.coerced > 0 {
+            eprintln!(
+                "  ! {}: {} values did not fit their column and were converted or written as NULL",
+                table_name, exported.coerced
+            );
+        }
     }
-    result
+    result.map(|exported| exported.rows)
 }
 
 fn print_export_summary(
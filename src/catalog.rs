@@ -0,0 +1,258 @@
+use std::ops::Bound;
+
+use turso_parser::ast;
+
+use crate::error;
+use crate::model::{Payload, TextEncoding};
+use crate::{
+    HashMap, Pager, Reader, SqlSchema, SqliteMasterTable, TreeWalk, SQLITE_MASTER_TABLE_SIZE,
+};
+
+/// what a sqlite_schema row describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Table,
+    /// a table implemented by a module, it has no b-tree of its own
+    VirtualTable,
+    Index,
+    View,
+    Trigger,
+}
+
+/// parsed `CREATE INDEX`
+pub struct IndexSchema {
+    pub unique: bool,
+    /// indexed columns or expressions, in key order
+    pub columns: Vec<ast::SortedColumn>,
+    /// `WHERE` clause of a partial index
+    pub where_clause: Option<Box<ast::Expr>>,
+}
+
+/// parsed `CREATE VIEW`
+pub struct ViewSchema {
+    /// column names given after the view name, empty when they come from the select
+    pub columns: Vec<ast::IndexedColumn>,
+    pub select: ast::Select,
+}
+
+/// parsed `CREATE TRIGGER`
+pub struct TriggerSchema {
+    pub time: Option<ast::TriggerTime>,
+    pub event: ast::TriggerEvent,
+    pub for_each_row: bool,
+    pub when_clause: Option<Box<ast::Expr>>,
+    pub commands: Vec<ast::TriggerCmd>,
+}
+
+/// parsed `CREATE VIRTUAL TABLE`
+pub struct VirtualTableSchema {
+    pub module_name: String,
+    /// module arguments, as written
+    pub args: Vec<String>,
+}
+
+/// the parsed CREATE statement of a schema object
+pub enum Definition {
+    Table(SqlSchema),
+    VirtualTable(VirtualTableSchema),
+    Index(IndexSchema),
+    View(ViewSchema),
+    Trigger(TriggerSchema),
+}
+
+/// a row of sqlite_schema
+pub struct SchemaObject {
+    pub kind: ObjectKind,
+    pub name: String,
+    /// table the object belongs to, tables and views belong to themselves
+    pub table_name: String,
+    /// root page of the b-tree, 0 for views, triggers and virtual tables
+    pub root_page: u32,
+    /// CREATE statement as stored, `None` for the automatic indexes of UNIQUE and PRIMARY KEY
+    pub sql: Option<String>,
+    /// `None` when there is no statement or the parser rejects it
+    pub definition: Option<Definition>,
+}
+
+impl SchemaObject {
+    /// parsed CREATE TABLE of a table
+    pub fn table_schema(&self) -> Option<&SqlSchema> {
+        match self.definition {
+            Some(Definition::Table(ref schema)) => Some(schema),
+            _ => None,
+        }
+    }
+
+    /// declared columns of a table or view, indexed columns of an index
+    ///
+    /// expressions in an index are given as written
+    pub fn column_names(&self) -> Vec<String> {
+        match self.definition {
+            Some(Definition::Table(ref schema)) => schema.get_column_names(),
+            Some(Definition::Index(ref index)) => index
+                .columns
+                .iter()
+                .map(|col| match *col.expr {
+                    ast::Expr::Id(ref name) | ast::Expr::Name(ref name) => name.as_str().to_owned(),
+                    ref expr => expr.to_string(),
+                })
+                .collect(),
+            Some(Definition::View(ref view)) => view
+                .columns
+                .iter()
+                .map(|col| col.col_name.as_str().to_owned())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// table constraints of a table
+    pub fn constraints(&self) -> &[ast::NamedTableConstraint] {
+        self.table_schema()
+            .map_or(&[], |schema| schema.constraints.as_slice())
+    }
+
+    /// indexes into [`SchemaObject::column_names`] of the PRIMARY KEY columns of a table
+    pub fn primary_key(&self) -> &[usize] {
+        self.table_schema()
+            .map_or(&[], |schema| schema.primary_key.as_slice())
+    }
+
+    /// index into [`SchemaObject::column_names`] of the column that aliases the rowid
+    pub fn rowid_alias(&self) -> Option<usize> {
+        self.table_schema()?.rowid_alias
+    }
+}
+
+/// parse a CREATE statement from sqlite_schema
+fn parse_definition(sql: &str, text_encoding: TextEncoding) -> Option<Definition> {
+    use turso_parser::parser::Parser;
+
+    let mut parser = Parser::new(sql.as_bytes());
+    let definition = match parser.next_cmd().ok()?? {
+        ast::Cmd::Stmt(ast::Stmt::CreateTable {
+            body:
+                ast::CreateTableBody::ColumnsAndConstraints {
+                    columns,
+                    constraints,
+                    options,
+                },
+            ..
+        }) => Definition::Table(SqlSchema::new(
+            columns,
+            constraints,
+            options.contains(ast::TableOptions::WITHOUT_ROWID),
+            text_encoding,
+        )),
+        ast::Cmd::Stmt(ast::Stmt::CreateVirtualTable(table)) => {
+            Definition::VirtualTable(VirtualTableSchema {
+                module_name: table.module_name.as_str().to_owned(),
+                args: table.args,
+            })
+        }
+        ast::Cmd::Stmt(ast::Stmt::CreateIndex {
+            unique,
+            columns,
+            where_clause,
+            ..
+        }) => Definition::Index(IndexSchema {
+            unique,
+            columns,
+            where_clause,
+        }),
+        ast::Cmd::Stmt(ast::Stmt::CreateView {
+            columns, select, ..
+        }) => Definition::View(ViewSchema { columns, select }),
+        ast::Cmd::Stmt(ast::Stmt::CreateTrigger {
+            time,
+            event,
+            for_each_row,
+            when_clause,
+            commands,
+            ..
+        }) => Definition::Trigger(TriggerSchema {
+            time,
+            event,
+            for_each_row,
+            when_clause,
+            commands,
+        }),
+        _ => return None,
+    };
+    Some(definition)
+}
+
+impl<P: Pager> Reader<P> {
+    /// every object in sqlite_schema, in the order they are stored
+    pub fn catalog(&self) -> error::Result<&[SchemaObject]> {
+        let objects = self.catalog.get_or_try_init(|| {
+            let mut objects = Vec::new();
+            self.stream_table_rows_from_page(
+                1,
+                &(Bound::Unbounded, Bound::Unbounded),
+                None,
+                &mut |_, column_values| {
+                    objects.extend(self.decode_schema_row(column_values));
+                    Ok(())
+                },
+                &mut HashMap::default(),
+                &mut TreeWalk::default(),
+            )?;
+            Ok::<_, error::SQLiteError>(objects)
+        })?;
+        Ok(objects)
+    }
+
+    /// the schema object named `name`, names are compared case-insensitively like SQLite does
+    pub fn schema_object(&self, name: &str) -> error::Result<Option<&SchemaObject>> {
+        Ok(self
+            .catalog()?
+            .iter()
+            .find(|object| object.name.eq_ignore_ascii_case(name)))
+    }
+
+    /// decode a sqlite_schema row, `None` if it does not have the expected shape
+    pub(crate) fn decode_schema_row(
+        &self,
+        column_values: &[Option<Payload<'_>>],
+    ) -> Option<SchemaObject> {
+        if column_values.len() != SQLITE_MASTER_TABLE_SIZE {
+            return None;
+        }
+
+        let text = |column: SqliteMasterTable| match column_values[column as usize] {
+            Some(Payload::Text(ref text)) => {
+                Some(text.decode(self.header.db_text_encoding).into_owned())
+            }
+            _ => None,
+        };
+
+        let root_page = column_values[SqliteMasterTable::RootPage as usize]
+            .as_ref()
+            .and_then(Payload::as_u32)
+            .unwrap_or(0);
+        let kind = match text(SqliteMasterTable::Type)?.as_str() {
+            "table" if root_page == 0 => ObjectKind::VirtualTable,
+            "table" => ObjectKind::Table,
+            "index" => ObjectKind::Index,
+            "view" => ObjectKind::View,
+            "trigger" => ObjectKind::Trigger,
+            _ => return None,
+        };
+        let name = text(SqliteMasterTable::Name)?;
+        let table_name = text(SqliteMasterTable::TblName).unwrap_or_else(|| name.clone());
+        let sql = text(SqliteMasterTable::Sql);
+        let definition = sql
+            .as_deref()
+            .and_then(|sql| parse_definition(sql, self.header.db_text_encoding));
+
+        Some(SchemaObject {
+            kind,
+            name,
+            table_name,
+            root_page,
+            sql,
+            definition,
+        })
+    }
+}
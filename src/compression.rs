@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+/// compression of a database file, recognised by its magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// the longest magic number, enough bytes for [`Compression::detect`]
+    pub const MAGIC_SIZE: usize = 6;
+
+    /// the compression of data starting with `bytes`, `None` for anything else such as a
+    /// plain database
+    pub fn detect(bytes: &[u8]) -> Option<Compression> {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+
+    /// decompress all of `source` into `sink`, concatenated gzip members and zstd frames
+    /// are read as one stream
+    pub fn decompress<R: Read, W: Write>(self, source: R, sink: &mut W) -> io::Result<()> {
+        match self {
+            Compression::Gzip => {
+                io::copy(&mut flate2::read::MultiGzDecoder::new(source), sink)?;
+                Ok(())
+            }
+            Compression::Zstd => zstd::stream::copy_decode(source, sink),
+            Compression::Xz => {
+                lzma_rs::xz_decompress(&mut BufReader::new(source), sink).map_err(|err| match err {
+                    lzma_rs::error::Error::IoError(err) => err,
+                    err => io::Error::new(io::ErrorKind::InvalidData, err),
+                })
+            }
+        }
+    }
+}
+
+/// the compression of `file`, which is left at its start
+fn sniff(file: &mut File) -> io::Result<Option<Compression>> {
+    let mut magic = Vec::with_capacity(Compression::MAGIC_SIZE);
+    Read::by_ref(file)
+        .take(Compression::MAGIC_SIZE as u64)
+        .read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(Compression::detect(&magic))
+}
+
+/// decompress `file` into an unnamed temporary file, it is removed once closed
+fn spill(compression: Compression, file: File) -> io::Result<File> {
+    let mut spill = tempfile::tempfile()?;
+    let mut sink = BufWriter::new(&mut spill);
+    compression.decompress(BufReader::new(file), &mut sink)?;
+    sink.flush()?;
+    drop(sink);
+    Ok(spill)
+}
+
+/// `file` itself, or a spill file with its contents when it is compressed
+pub(crate) fn open_decompressed(mut file: File) -> io::Result<File> {
+    match sniff(&mut file)? {
+        Some(compression) => spill(compression, file),
+        None => Ok(file),
+    }
+}
+
+/// `buf` itself, or its contents when it is compressed
+pub(crate) fn decompressed(buf: Vec<u8>) -> io::Result<Vec<u8>> {
+    match Compression::detect(&buf) {
+        Some(compression) => {
+            let mut out = Vec::with_capacity(buf.len() * 4);
+            compression.decompress(&buf[..], &mut out)?;
+            Ok(out)
+        }
+        None => Ok(buf),
+    }
+}
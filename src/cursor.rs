@@ -0,0 +1,268 @@
+use std::ops::{Bound, Range, RangeBounds};
+
+use crate::error::{self, CorruptionKind, SQLiteError};
+use crate::model::{self, Page};
+use crate::parser::{self, ParseFailure};
+use crate::{
+    payload_offset, subtree_in_range, PageRef, Pager, Reader, SqlSchema, TreeWalk, HEADER_SIZE,
+};
+
+/// a row of a rowid table, the record is decoded on demand
+pub struct TableRow<'r> {
+    pub rowid: i64,
+    pageno: u32,
+    /// offset of the cell payload within its page, for error reporting
+    offset: usize,
+    record: Record<'r>,
+    /// supplies the rowid alias and the columns added after the row was written
+    schema: Option<&'r SqlSchema>,
+}
+
+impl<'r> TableRow<'r> {
+    /// the decoded columns, with the rowid in its INTEGER PRIMARY KEY column and the
+    /// DEFAULT of columns added after the row was written
+    pub fn values(&self) -> error::Result<Vec<Option<model::Payload<'_>>>> {
+        let mut values = Vec::new();
+        self.decode_into(&mut values)?;
+        Ok(values)
+    }
+
+    /// decode the columns into `values`, reusing its allocation
+    pub fn decode_into<'s>(
+        &'s self,
+        values: &mut Vec<Option<model::Payload<'s>>>,
+    ) -> error::Result<()> {
+        let mut input = self.record.bytes();
+        parser::record::<ParseFailure>(&mut input, values).map_err(|err| SQLiteError::Corrupt {
+            page: self.pageno,
+            offset: self.offset,
+            kind: err.kind,
+        })?;
+        if let Some(schema) = self.schema {
+            schema.complete_row(self.rowid, values);
+        }
+        Ok(())
+    }
+}
+
+/// the whole record of a row
+enum Record<'r> {
+    /// stored entirely on its leaf page, at the given range
+    Local(PageRef<'r>, Range<usize>),
+    /// joined with its overflow chain
+    Joined(Vec<u8>),
+}
+
+impl Record<'_> {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Record::Local(page, range) => &page[range.clone()],
+            Record::Joined(record) => record,
+        }
+    }
+}
+
+/// a leaf cell located by offsets, so the frame can own the page it points into
+struct LeafCell {
+    rowid: i64,
+    payload_size: u64,
+    /// the part of the payload stored on the leaf page
+    local_payload: Range<usize>,
+    overflow_page_no: Option<u32>,
+}
+
+impl LeafCell {
+    fn new(page: &[u8], cell: &model::LeafTableCell<'_>) -> Self {
+        let start = payload_offset(page, cell.local_payload);
+        LeafCell {
+            rowid: cell.rowid as i64,
+            payload_size: cell.payload_size,
+            local_payload: start..start + cell.local_payload.len(),
+            overflow_page_no: cell.overflow_page_no,
+        }
+    }
+}
+
+/// a table b-tree page on the cursor stack
+enum Frame<'r> {
+    Interior {
+        cells: std::vec::IntoIter<model::InteriorCell>,
+        /// taken once it has been descended into
+        rightmost: Option<u32>,
+        /// key of the previous cell, the next child only holds rowids above it
+        previous_key: Option<i64>,
+    },
+    Leaf {
+        pageno: u32,
+        page: PageRef<'r>,
+        cells: std::vec::IntoIter<LeafCell>,
+    },
+}
+
+/// cursor over the rows of a rowid table, in rowid order
+///
+/// the path from the root to the current leaf is kept on an explicit stack, so the
+/// cursor can be paused, dropped early or advanced in step with another one
+pub struct TableRows<'r, P: Pager> {
+    reader: &'r Reader<P>,
+    schema: Option<&'r SqlSchema>,
+    rowids: (Bound<i64>, Bound<i64>),
+    stack: Vec<Frame<'r>>,
+    walk: TreeWalk,
+}
+
+impl<P: Pager> Reader<P> {
+    /// cursor over every row of `table_name`
+    pub fn table_rows(&self, table_name: &str) -> error::Result<TableRows<'_, P>> {
+        self.table_rows_range(table_name, ..)
+    }
+
+    /// cursor over the rows of `table_name` whose rowid is in `rowids`
+    pub fn table_rows_range(
+        &self,
+        table_name: &str,
+        rowids: impl RangeBounds<i64>,
+    ) -> error::Result<TableRows<'_, P>> {
+        let root_pageno = self.rowid_table_root(table_name)?;
+
+        let mut rows = TableRows {
+            reader: self,
+            schema: self.table_schema(table_name)?,
+            rowids: (rowids.start_bound().cloned(), rowids.end_bound().cloned()),
+            stack: Vec::new(),
+            walk: TreeWalk::default(),
+        };
+        rows.push(root_pageno)?;
+        Ok(rows)
+    }
+}
+
+impl<'r, P: Pager> TableRows<'r, P> {
+    fn push(&mut self, pageno: u32) -> error::Result<()> {
+        self.walk.enter(pageno)?;
+
+        let page = self.reader.page_bytes(pageno)?;
+        let cells = match self.reader.parse_page(pageno, &page)? {
+            Page::InteriorTable(interior) => {
+                self.stack.push(Frame::Interior {
+                    cells: interior.cells.into_iter(),
+                    rightmost: Some(interior.header.rightmost_pointer),
+                    previous_key: None,
+                });
+                return Ok(());
+            }
+            Page::LeafTable(leaf) => leaf
+                .cells
+                .iter()
+                .map(|cell| LeafCell::new(&page, cell))
+                .collect::<Vec<_>>(),
+            // an index page in a table b-tree
+            _ => {
+                let page_start_offset = if pageno <= 1 { HEADER_SIZE } else { 0 };
+                return Err(SQLiteError::Corrupt {
+                    page: pageno,
+                    offset: page_start_offset,
+                    kind: CorruptionKind::InvalidPageType(page[page_start_offset]),
+                });
+            }
+        };
+
+        self.stack.push(Frame::Leaf {
+            pageno,
+            page,
+            cells: cells.into_iter(),
+        });
+        Ok(())
+    }
+
+    fn pop(&mut self) {
+        self.stack.pop();
+        self.walk.leave();
+    }
+
+    /// advance to the next row in range, `None` once the tree is exhausted
+    fn advance(&mut self) -> error::Result<Option<TableRow<'r>>> {
+        loop {
+            let child = match self.stack.last_mut() {
+                None => return Ok(None),
+                Some(Frame::Leaf {
+                    pageno,
+                    page,
+                    cells,
+                }) => match cells.find(|cell| self.rowids.contains(&cell.rowid)) {
+                    Some(cell) => {
+                        return self
+                            .reader
+                            .table_row(*pageno, page.clone(), cell, self.schema)
+                            .map(Some)
+                    }
+                    None => None,
+                },
+                Some(Frame::Interior {
+                    cells,
+                    rightmost,
+                    previous_key,
+                }) => {
+                    let rowids = &self.rowids;
+                    let next = cells.find_map(|cell| {
+                        let after = previous_key.replace(cell.integer_key);
+                        subtree_in_range(rowids, after, Some(cell.integer_key))
+                            .then_some(cell.left_child_page_no)
+                    });
+                    match next {
+                        Some(pageno) => Some(pageno),
+                        None if subtree_in_range(rowids, *previous_key, None) => rightmost.take(),
+                        None => None,
+                    }
+                }
+            };
+
+            match child {
+                Some(pageno) => self.push(pageno)?,
+                None => self.pop(),
+            }
+        }
+    }
+}
+
+impl<'r, P: Pager> Iterator for TableRows<'r, P> {
+    type Item = error::Result<TableRow<'r>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = self.advance();
+        // nothing sensible can follow a corrupt page
+        if row.is_err() {
+            self.stack.clear();
+        }
+        row.transpose()
+    }
+}
+
+impl<P: Pager> Reader<P> {
+    /// the full record of a leaf cell, joined with its overflow chain when it has one
+    fn table_row<'r>(
+        &'r self,
+        pageno: u32,
+        page: PageRef<'r>,
+        cell: LeafCell,
+        schema: Option<&'r SqlSchema>,
+    ) -> error::Result<TableRow<'r>> {
+        let offset = cell.local_payload.start;
+        let record = match cell.overflow_page_no {
+            None => Record::Local(page, cell.local_payload),
+            Some(first_page) => Record::Joined(self.join_overflow(
+                &page[cell.local_payload],
+                first_page,
+                cell.payload_size,
+            )?),
+        };
+
+        Ok(TableRow {
+            rowid: cell.rowid,
+            pageno,
+            offset,
+            record,
+            schema,
+        })
+    }
+}
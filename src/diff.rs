@@ -0,0 +1,82 @@
+use crate::catalog::ObjectKind;
+use crate::error;
+use crate::{Pager, Reader, TableRow, TableRows};
+
+/// how a row differs between two readers of a table
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RowChange {
+    /// the rowid only exists in the newer reader
+    Inserted,
+    /// the rowid exists in both, with other values
+    Updated,
+    /// the rowid only exists in the older reader
+    Deleted,
+}
+
+impl<P: Pager> Reader<P> {
+    /// rows of `table_name` that differ between this reader and `newer`, in rowid order,
+    /// typically the same database read at two commits of its WAL
+    ///
+    /// `callback` gets the row as it is here and as it is in `newer`, `None` on the side
+    /// without it, a table that only exists on one side has all its rows inserted or deleted
+    pub fn diff_table<Q: Pager, F>(
+        &self,
+        newer: &Reader<Q>,
+        table_name: &str,
+        mut callback: F,
+    ) -> error::Result<()>
+    where
+        F: FnMut(RowChange, Option<&TableRow<'_>>, Option<&TableRow<'_>>) -> error::Result<()>,
+    {
+        let mut old_rows = table_rows_if_exists(self, table_name)?;
+        let mut new_rows = table_rows_if_exists(newer, table_name)?;
+        if old_rows.is_none() && new_rows.is_none() {
+            // not found on either side, report it like any other lookup
+            self.rowid_table_root(table_name)?;
+        }
+
+        let mut old = next_row(&mut old_rows)?;
+        let mut new = next_row(&mut new_rows)?;
+        loop {
+            match (&old, &new) {
+                (None, None) => return Ok(()),
+                (Some(old_row), Some(new_row)) if old_row.rowid == new_row.rowid => {
+                    if old_row.values()? != new_row.values()? {
+                        callback(RowChange::Updated, Some(old_row), Some(new_row))?;
+                    }
+                    old = next_row(&mut old_rows)?;
+                    new = next_row(&mut new_rows)?;
+                }
+                (Some(old_row), new_row)
+                    if new_row
+                        .as_ref()
+                        .is_none_or(|new_row| old_row.rowid < new_row.rowid) =>
+                {
+                    callback(RowChange::Deleted, Some(old_row), None)?;
+                    old = next_row(&mut old_rows)?;
+                }
+                (_, new_row) => {
+                    callback(RowChange::Inserted, None, new_row.as_ref())?;
+                    new = next_row(&mut new_rows)?;
+                }
+            }
+        }
+    }
+}
+
+/// cursor over every row of `table_name`, `None` when `reader` has no such table
+fn table_rows_if_exists<'r, P: Pager>(
+    reader: &'r Reader<P>,
+    table_name: &str,
+) -> error::Result<Option<TableRows<'r, P>>> {
+    if reader.find_object(ObjectKind::Table, table_name)?.is_none() {
+        return Ok(None);
+    }
+    reader.table_rows(table_name).map(Some)
+}
+
+fn next_row<'r, P: Pager>(
+    rows: &mut Option<TableRows<'r, P>>,
+) -> error::Result<Option<TableRow<'r>>> {
+    rows.as_mut().and_then(Iterator::next).transpose()
+}
@@ -17,10 +17,60 @@ pub enum SQLiteError {
     #[error("Table {0}, not found")]
     TableNotFound(String),
 
+    #[error("corrupt database: {kind} (page {page}, offset {offset})")]
+    Corrupt {
+        page: u32,
+        offset: usize,
+        kind: CorruptionKind,
+    },
+
+    #[error("{found} value in column `{column}` does not fit {expected} (rowid {rowid:?})")]
+    TypeMismatch {
+        /// `None` for WITHOUT ROWID tables
+        rowid: Option<i64>,
+        column: String,
+        found: &'static str,
+        expected: &'static str,
+    },
+
+    #[error("hot journal with {0} saved pages, the database may be half written")]
+    HotJournal(usize),
+
     #[error("{0}")]
     Other(String),
 }
 
+/// what was wrong with a corrupt or truncated page
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum CorruptionKind {
+    #[error("page is past the end of the file")]
+    PageOutOfRange,
+
+    #[error("invalid page size {0}")]
+    InvalidPageSize(usize),
+
+    #[error("unknown or unexpected page type {0:#04x}")]
+    InvalidPageType(u8),
+
+    #[error("cell pointer {0} is outside the page")]
+    CellPointerOutOfRange(u16),
+
+    #[error("record header size {0} is larger than the payload")]
+    InvalidHeaderSize(u64),
+
+    #[error("reserved serial type")]
+    ReservedSerialType,
+
+    #[error("page is reached twice, the b-tree or overflow chain loops")]
+    PageCycle,
+
+    #[error("b-tree is deeper than {0} levels")]
+    TreeTooDeep(usize),
+
+    #[error("truncated or malformed data")]
+    Malformed,
+}
+
 pub type Result<T> = std::result::Result<T, SQLiteError>;
 
 impl<I> From<ParseError<I, ContextError>> for SQLiteError
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use crate::error::{self, CorruptionKind, SQLiteError};
+use crate::model;
+use crate::parser::{self, carve_leaf_table_cells, freelist_trunk_page, ParseFailure};
+use crate::{HashMap, HashSet, Pager, Reader, HEADER_SIZE};
+
+/// role of a page on the freelist
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FreePageKind {
+    /// lists leaf pages and links to the next trunk page, only the start of the page is
+    /// overwritten
+    Trunk,
+    /// unused, its old content stays until the page is reused
+    Leaf,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FreePage {
+    pub pageno: u32,
+    pub kind: FreePageKind,
+}
+
+impl<P: Pager> Reader<P> {
+    /// every page on the freelist, each trunk page followed by the leaf pages it lists
+    pub fn freelist(&self) -> error::Result<Vec<FreePage>> {
+        let mut pages = Vec::with_capacity(self.header.total_freelist_pages as usize);
+        let mut visited = HashSet::default();
+        // the next trunk and the leaf count come before the leaf page numbers
+        let max_leaves = self.header.usable_page_size() / 4 - 2;
+        let mut next_trunk = Some(self.header.first_freelist_page_no).filter(|&pageno| pageno != 0);
+
+        while let Some(trunk) = next_trunk {
+            if !visited.insert(trunk) {
+                return Err(SQLiteError::Corrupt {
+                    page: trunk,
+                    offset: 0,
+                    kind: CorruptionKind::PageCycle,
+                });
+            }
+
+            let page_bytes = self.page_bytes(trunk)?;
+            let mut input = &*page_bytes;
+            let (next, leaves) = freelist_trunk_page::<ParseFailure>(&mut input, max_leaves)
+                .map_err(|err| err.at_page(trunk, &page_bytes, 0))?;
+
+            pages.push(FreePage {
+                pageno: trunk,
+                kind: FreePageKind::Trunk,
+            });
+            for leaf in leaves {
+                if !visited.insert(leaf) {
+                    return Err(SQLiteError::Corrupt {
+                        page: leaf,
+                        offset: 0,
+                        kind: CorruptionKind::PageCycle,
+                    });
+                }
+                pages.push(FreePage {
+                    pageno: leaf,
+                    kind: FreePageKind::Leaf,
+                });
+            }
+            next_trunk = next;
+        }
+
+        Ok(pages)
+    }
+
+    /// rows of deleted records still intact on free leaf pages that used to be table leaves
+    ///
+    /// the records belong to no table anymore, `callback` gets the free page each was found
+    /// on, cells and overflow chains that don't decode are skipped and so are leaf pages
+    /// past the end of the file
+    pub fn carve_free_pages<F>(&self, mut callback: F) -> error::Result<()>
+    where
+        F: FnMut(
+            u32,
+            &model::LeafTableCell<'_>,
+            &Vec<Option<model::Payload<'_>>>,
+        ) -> error::Result<()>,
+    {
+        let mut cached_types = HashMap::default();
+
+        for free_page in self.freelist()? {
+            if free_page.kind != FreePageKind::Leaf {
+                continue;
+            }
+
+            // a leaf listed past the end of a truncated file holds nothing to carve
+            let pageno = free_page.pageno;
+            let page_bytes = match self.page_bytes(pageno) {
+                Ok(page_bytes) => page_bytes,
+                Err(SQLiteError::Corrupt {
+                    kind: CorruptionKind::PageOutOfRange,
+                    ..
+                }) => continue,
+                Err(err) => return Err(err),
+            };
+            self.carve_table_leaf(
+                pageno,
+                &page_bytes,
+                &mut cached_types,
+                |cell, column_values| callback(pageno, cell, column_values),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// cells of a table leaf page that decode, joined with their overflow chains, whether
+    /// or not the page is part of a b-tree
+    ///
+    /// nothing is found on a page of any other kind, cells and overflow chains that don't
+    /// decode are skipped
+    pub(crate) fn carve_table_leaf<F>(
+        &self,
+        pageno: u32,
+        page_bytes: &[u8],
+        cached_types: &mut HashMap<u64, Arc<Vec<model::SerialType>>>,
+        mut callback: F,
+    ) -> error::Result<()>
+    where
+        F: FnMut(
+            &model::LeafTableCell<'_>,
+            &mut Vec<Option<model::Payload<'_>>>,
+        ) -> error::Result<()>,
+    {
+        let page_start_offset = if pageno == 1 { HEADER_SIZE } else { 0 };
+        let mut column_values = Vec::new();
+        carve_leaf_table_cells(
+            &page_bytes[page_start_offset..],
+            page_start_offset,
+            &self.header,
+            &mut column_values,
+            cached_types,
+            |mut cell, column_values| {
+                if cell.overflow_page_no.is_none() {
+                    return callback(&cell, column_values);
+                }
+
+                // the overflow pages of a deleted or orphaned record may be reused by now
+                let Ok(full_payload) = self.reconstruct_full_payload(&cell) else {
+                    return Ok(());
+                };
+                let mut input = full_payload.as_slice();
+                let mut overflow_values = Vec::new();
+                let Ok(types) = parser::record::<ParseFailure>(&mut input, &mut overflow_values)
+                else {
+                    return Ok(());
+                };
+                cell.payload.column_types = Arc::new(types);
+                callback(&cell, &mut overflow_values)
+            },
+        )
+    }
+}
@@ -0,0 +1,206 @@
+use memmap2::MmapOptions;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use winnow::error::ContextError;
+
+use crate::error;
+use crate::model::JournalHeader;
+use crate::parser::{journal_header, JOURNAL_HEADER_SIZE};
+use crate::HashMap;
+
+/// what to do when a database has a hot rollback journal
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HotJournal {
+    /// read the database file as it is, warning about the journal is left to the caller,
+    /// see [`crate::Reader::hot_journal`]
+    #[default]
+    Warn,
+    /// fail with [`error::SQLiteError::HotJournal`]
+    Refuse,
+    /// read the pages saved in the journal in place of the database file, the state of the
+    /// last commit, like SQLite's rollback but without writing anything
+    Rollback,
+}
+
+impl std::str::FromStr for HotJournal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(HotJournal::Warn),
+            "refuse" => Ok(HotJournal::Refuse),
+            "rollback" => Ok(HotJournal::Rollback),
+            _ => Err(format!(
+                "unknown mode `{s}`, expected warn, refuse or rollback"
+            )),
+        }
+    }
+}
+
+/// original image of a page, saved before the transaction changed it
+struct JournalRecord {
+    pageno: u32,
+    offset: usize,
+}
+
+/// rollback journal left behind by a writer that didn't finish its transaction
+///
+/// only page records that pass their checksum are kept, like SQLite's playback stops at
+/// the first bad one, the first image saved of a page wins
+pub struct Journal {
+    buf: Arc<dyn AsRef<[u8]> + Send + Sync>,
+    pub header: JournalHeader,
+    records: Vec<JournalRecord>,
+    page_index: HashMap<u32, usize>,
+}
+
+/// `<db>-journal` next to the database file
+pub(crate) fn journal_path(database: &Path) -> PathBuf {
+    let mut path = database.as_os_str().to_owned();
+    path.push("-journal");
+    PathBuf::from(path)
+}
+
+/// the nonce plus every 200th byte counted back from the end of the page
+#[inline(always)]
+fn checksum(nonce: u32, page: &[u8]) -> u32 {
+    (page.len() % 200..page.len())
+        .step_by(200)
+        .filter(|&idx| idx > 0)
+        .fold(nonce, |sum, idx| sum.wrapping_add(page[idx] as u32))
+}
+
+impl Journal {
+    /// map the journal at `path`, `None` unless it exists with a valid header, a journal that
+    /// was truncated or had its header zeroed after a commit is not hot
+    pub fn open<P: AsRef<Path>>(path: P) -> error::Result<Option<Journal>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        if (file.metadata()?.len() as usize) < JOURNAL_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let mmap = unsafe { MmapOptions::new().map(&file) }?;
+        Ok(Journal::from_source(mmap))
+    }
+
+    /// read the journal at `path` into memory, like [`Journal::open`] without a memory map
+    pub fn read<P: AsRef<Path>>(path: P) -> error::Result<Option<Journal>> {
+        let buf = match std::fs::read(path) {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Journal::from_source(buf))
+    }
+
+    /// parse a journal from any byte source, `None` without a valid first header
+    pub fn from_source<S: AsRef<[u8]> + Send + Sync + 'static>(buf: S) -> Option<Journal> {
+        let bytes = buf.as_ref();
+        let header = segment_header(bytes)?;
+        let page_size = header.page_size as usize;
+        let record_size = 4 + page_size + 4;
+
+        let mut records = Vec::new();
+        let mut page_index = HashMap::default();
+        let mut segment = Some((0, header.clone()));
+
+        'segments: while let Some((start, current)) = segment.take() {
+            if current.page_size != header.page_size {
+                break;
+            }
+
+            let sector_size = current.sector_size as usize;
+            let mut offset = start + sector_size;
+            let record_count = match current.record_count {
+                u32::MAX => bytes.len().saturating_sub(offset) / record_size,
+                count => count as usize,
+            };
+
+            for _ in 0..record_count {
+                let Some(record) = bytes.get(offset..offset + record_size) else {
+                    break 'segments;
+                };
+                let pageno = u32::from_be_bytes([record[0], record[1], record[2], record[3]]);
+                let page = &record[4..4 + page_size];
+                let sum = u32::from_be_bytes([
+                    record[4 + page_size],
+                    record[5 + page_size],
+                    record[6 + page_size],
+                    record[7 + page_size],
+                ]);
+                if pageno == 0 || sum != checksum(current.checksum_nonce, page) {
+                    break 'segments;
+                }
+
+                // pages past the old end of the file are cut off by the rollback
+                if pageno <= header.db_size {
+                    page_index.entry(pageno).or_insert(records.len());
+                }
+                records.push(JournalRecord {
+                    pageno,
+                    offset: offset + 4,
+                });
+                offset += record_size;
+            }
+
+            if current.record_count == u32::MAX {
+                break;
+            }
+
+            // the next segment starts at a sector boundary
+            let next = offset.next_multiple_of(sector_size);
+            segment = bytes
+                .get(next..)
+                .and_then(segment_header)
+                .map(|header| (next, header));
+        }
+
+        Some(Journal {
+            buf: Arc::new(buf),
+            header,
+            records,
+            page_index,
+        })
+    }
+
+    /// number of page records that passed their checksum
+    pub fn record_count(&self) -> usize {
+        self.records.len()
+    }
+
+    /// size of the database in pages once the journal is rolled back
+    pub fn db_size(&self) -> u32 {
+        self.header.db_size
+    }
+
+    /// pages the transaction changed, in the order their images were saved
+    pub fn pages(&self) -> impl Iterator<Item = u32> + '_ {
+        self.records.iter().map(|record| record.pageno)
+    }
+
+    /// original image of `pageno`, if the transaction changed it
+    #[inline(always)]
+    pub(crate) fn page(&self, pageno: u32) -> Option<&[u8]> {
+        let record = &self.records[*self.page_index.get(&pageno)?];
+        let bytes = (*self.buf).as_ref();
+        Some(&bytes[record.offset..record.offset + self.header.page_size as usize])
+    }
+}
+
+/// header at the start of `bytes`, if it is valid
+fn segment_header(bytes: &[u8]) -> Option<JournalHeader> {
+    let mut input = bytes;
+    let header = journal_header::<ContextError>(&mut input).ok()?;
+    let valid_page_size =
+        header.page_size.is_power_of_two() && (512..=65536).contains(&header.page_size);
+    let valid_sector_size =
+        header.sector_size.is_power_of_two() && (32..=65536).contains(&header.sector_size);
+    (valid_page_size && valid_sector_size).then_some(header)
+}
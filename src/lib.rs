@@ -4,59 +4,188 @@ static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
 extern crate core;
 
 use memmap2::{Mmap, MmapOptions};
-use once_cell::unsync::OnceCell;
+use once_cell::sync::OnceCell;
 use std::fs::File;
+use std::io::Read;
+use std::ops::{Bound, RangeBounds};
 use std::path::Path;
 
-use winnow::error::ContextError;
-
-use crate::error::SQLiteError;
+use crate::catalog::{ObjectKind, SchemaObject};
+use crate::error::{CorruptionKind, SQLiteError};
+use crate::journal::{HotJournal, Journal};
 use crate::model::{DbHeader, Page};
-use crate::parser::{db_header, overflow_page};
+use crate::parser::{db_header, overflow_page, ParseFailure};
+use crate::wal::Wal;
+
+pub use crate::cursor::{TableRow, TableRows};
+pub use crate::pager::{PageRef, Pager, PreadPager};
 
+pub mod catalog;
+pub mod compression;
+mod cursor;
+pub mod diff;
 pub mod error;
+pub mod freelist;
+pub mod journal;
 pub mod model;
+mod pager;
+mod parallel;
 pub mod parquet_writer;
 mod parser;
+pub mod recover;
+pub mod salvage;
+pub mod wal;
 
 const HEADER_SIZE: usize = 100;
 
 pub type HashMap<K, V> = std::collections::HashMap<K, V, ahash::RandomState>;
+type HashSet<K> = std::collections::HashSet<K, ahash::RandomState>;
+
+/// SQLite refuses b-trees deeper than this (`BTCURSOR_MAX_DEPTH`)
+const MAX_TREE_DEPTH: usize = 20;
 
 const SQLITE_MASTER_TABLE_SIZE: usize = 5;
 
+/// sqlite_schema has no CREATE statement of its own
+const SQLITE_SCHEMA_SQL: &str =
+    "CREATE TABLE sqlite_schema(type text, name text, tbl_name text, rootpage integer, sql text)";
+
 #[repr(usize)]
 enum SqliteMasterTable {
     Type = 0,
     Name = 1,
-    // TblName = 2,
+    TblName = 2,
     RootPage = 3,
     Sql = 4,
 }
 
+/// pages entered by a single b-tree traversal
+///
+/// every page of a b-tree has exactly one parent, seeing a page twice means the tree loops
+#[derive(Default)]
+struct TreeWalk {
+    visited: HashSet<u32>,
+    depth: usize,
+}
+
+impl TreeWalk {
+    #[inline(always)]
+    fn enter(&mut self, pageno: u32) -> error::Result<()> {
+        if self.depth >= MAX_TREE_DEPTH {
+            return Err(SQLiteError::Corrupt {
+                page: pageno,
+                offset: 0,
+                kind: CorruptionKind::TreeTooDeep(MAX_TREE_DEPTH),
+            });
+        }
+        self.visit(pageno)?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// mark `pageno` as seen without descending into it
+    #[inline(always)]
+    fn visit(&mut self, pageno: u32) -> error::Result<()> {
+        if !self.visited.insert(pageno) {
+            return Err(SQLiteError::Corrupt {
+                page: pageno,
+                offset: 0,
+                kind: CorruptionKind::PageCycle,
+            });
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+/// whether the subtree holding the rowids in `(after, up_to]` can hold a rowid in `rowids`,
+/// `None` leaves that side unbounded
 #[inline(always)]
-fn get_table_cell_values<'a>(
-    cell: &'a model::LeafTableCell<'a>,
-) -> &'a [Option<model::Payload<'a>>] {
-    cell.column_values.as_deref().unwrap_or_default()
+fn subtree_in_range(
+    rowids: &(Bound<i64>, Bound<i64>),
+    after: Option<i64>,
+    up_to: Option<i64>,
+) -> bool {
+    let below_start = up_to.is_some_and(|up_to| match rowids.0 {
+        Bound::Included(start) => up_to < start,
+        Bound::Excluded(start) => up_to <= start,
+        Bound::Unbounded => false,
+    });
+    let past_end = after.is_some_and(|after| match rowids.1 {
+        Bound::Included(end) => after >= end,
+        Bound::Excluded(end) => after.saturating_add(1) >= end,
+        Bound::Unbounded => false,
+    });
+
+    !below_start && !past_end
+}
+
+/// offset of `local_payload` within `page`, 0 if it does not point into the page
+fn payload_offset(page: &[u8], local_payload: &[u8]) -> usize {
+    (local_payload.as_ptr() as usize)
+        .checked_sub(page.as_ptr() as usize)
+        .filter(|&offset| offset < page.len())
+        .unwrap_or(0)
 }
 
+/// error for a record that failed to decode, `local_payload` locates the cell within `page`
+#[cold]
+fn corrupt_record(
+    pageno: u32,
+    page: &[u8],
+    local_payload: &[u8],
+    kind: CorruptionKind,
+) -> SQLiteError {
+    SQLiteError::Corrupt {
+        page: pageno,
+        offset: payload_offset(page, local_payload),
+        kind,
+    }
+}
+
+#[derive(Clone)]
 pub struct SqlSchema {
     pub columns: Vec<turso_parser::ast::ColumnDefinition>,
+    /// table constraints that follow the column definitions
+    pub constraints: Vec<turso_parser::ast::NamedTableConstraint>,
+    /// the table is stored as an index b-tree keyed by its PRIMARY KEY
+    pub without_rowid: bool,
+    /// indexes into `columns` of the PRIMARY KEY columns, in key order
+    pub primary_key: Vec<usize>,
+    /// index into `columns` of the INTEGER PRIMARY KEY, the column is the rowid itself
+    /// and the record only stores a NULL for it
+    pub rowid_alias: Option<usize>,
+    /// constant DEFAULT of every column, in declared order
+    defaults: Vec<Option<model::DefaultValue>>,
 }
 
+/// parsed with UTF-8 as the text encoding of the DEFAULT values
 impl TryFrom<String> for SqlSchema {
     type Error = SQLiteError;
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        use turso_parser::ast::{Cmd, CreateTableBody, Stmt};
+        use turso_parser::ast::{Cmd, CreateTableBody, Stmt, TableOptions};
         use turso_parser::parser::Parser;
         let mut parser = Parser::new(value.as_bytes());
 
         match parser.next_cmd() {
             Ok(Some(Cmd::Stmt(Stmt::CreateTable {
-                body: CreateTableBody::ColumnsAndConstraints { columns, .. },
+                body:
+                    CreateTableBody::ColumnsAndConstraints {
+                        columns,
+                        constraints,
+                        options,
+                    },
                 ..
-            }))) => Ok(SqlSchema { columns }),
+            }))) => Ok(SqlSchema::new(
+                columns,
+                constraints,
+                options.contains(TableOptions::WITHOUT_ROWID),
+                model::TextEncoding::Utf8,
+            )),
             Err(err) => Err(SQLiteError::SqlQueryErr(err)),
             _ => Err(SQLiteError::ParsingError(format!(
                 "Unexpected SQL query: {value}"
@@ -65,52 +194,530 @@ impl TryFrom<String> for SqlSchema {
     }
 }
 
+fn primary_key_columns(
+    columns: &[turso_parser::ast::ColumnDefinition],
+    constraints: &[turso_parser::ast::NamedTableConstraint],
+) -> Vec<usize> {
+    use turso_parser::ast::{ColumnConstraint, Expr, TableConstraint};
+
+    let column_pk = columns.iter().position(|col| {
+        col.constraints
+            .iter()
+            .any(|c| matches!(c.constraint, ColumnConstraint::PrimaryKey { .. }))
+    });
+    if let Some(idx) = column_pk {
+        return vec![idx];
+    }
+
+    let mut primary_key = Vec::new();
+    for constraint in constraints {
+        if let TableConstraint::PrimaryKey {
+            columns: ref pk, ..
+        } = constraint.constraint
+        {
+            for sorted_column in pk {
+                let name = match *sorted_column.expr {
+                    Expr::Id(ref name) | Expr::Name(ref name) => name.as_str(),
+                    Expr::Collate(ref expr, _) => match **expr {
+                        Expr::Id(ref name) | Expr::Name(ref name) => name.as_str(),
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                let idx = columns
+                    .iter()
+                    .position(|col| col.col_name.as_str().eq_ignore_ascii_case(name));
+                if let Some(idx) = idx.filter(|idx| !primary_key.contains(idx)) {
+                    primary_key.push(idx);
+                }
+            }
+        }
+    }
+    primary_key
+}
+
+/// the INTEGER PRIMARY KEY column of a rowid table, if it has one
+///
+/// only a column declared exactly `INTEGER` aliases the rowid, and like SQLite a column
+/// constraint `PRIMARY KEY DESC` does not while a table constraint with DESC does
+fn rowid_alias_column(
+    columns: &[turso_parser::ast::ColumnDefinition],
+    primary_key: &[usize],
+    without_rowid: bool,
+) -> Option<usize> {
+    use turso_parser::ast::{ColumnConstraint, SortOrder};
+
+    let &[idx] = primary_key else { return None };
+    let column = &columns[idx];
+    let integer = column
+        .col_type
+        .as_ref()
+        .is_some_and(|col_type| col_type.name.eq_ignore_ascii_case("INTEGER"));
+    let descending = column.constraints.iter().any(|c| {
+        matches!(
+            c.constraint,
+            ColumnConstraint::PrimaryKey {
+                order: Some(SortOrder::Desc),
+                ..
+            }
+        )
+    });
+
+    (!without_rowid && integer && !descending).then_some(idx)
+}
+
+/// value of a DEFAULT expression, `None` for NULL and anything that is not a constant
+fn default_value(
+    expr: &turso_parser::ast::Expr,
+    text_encoding: model::TextEncoding,
+) -> Option<model::DefaultValue> {
+    use turso_parser::ast::{Expr, Literal, UnaryOperator};
+
+    fn numeric(literal: &str, negative: bool) -> Option<model::DefaultValue> {
+        let hex = literal
+            .strip_prefix("0x")
+            .or_else(|| literal.strip_prefix("0X"));
+        let integer = match hex {
+            Some(hex) => u64::from_str_radix(hex, 16).ok().map(|n| n as i64),
+            None => literal.parse::<i64>().ok(),
+        };
+        match integer {
+            Some(n) if negative => Some(model::DefaultValue::I64(n.wrapping_neg())),
+            Some(n) => Some(model::DefaultValue::I64(n)),
+            // integers that don't fit an i64 are stored as REAL
+            None => {
+                let n = literal.parse::<f64>().ok()?;
+                Some(model::DefaultValue::F64(if negative { -n } else { n }))
+            }
+        }
+    }
+
+    match expr {
+        Expr::Parenthesized(exprs) if exprs.len() == 1 => default_value(&exprs[0], text_encoding),
+        Expr::Unary(op @ (UnaryOperator::Negative | UnaryOperator::Positive), expr) => match **expr
+        {
+            Expr::Literal(Literal::Numeric(ref literal)) => {
+                numeric(literal, *op == UnaryOperator::Negative)
+            }
+            _ => None,
+        },
+        Expr::Literal(Literal::Numeric(literal)) => numeric(literal, false),
+        Expr::Literal(Literal::String(literal)) => {
+            let text = literal
+                .strip_prefix('\'')
+                .and_then(|text| text.strip_suffix('\''))
+                .unwrap_or(literal)
+                .replace("''", "'");
+            Some(model::DefaultValue::Text(text_encoding.encode(&text)))
+        }
+        Expr::Literal(Literal::Blob(hex)) => {
+            let blob = (0..hex.len() / 2)
+                .map(|i| u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            Some(model::DefaultValue::Blob(blob))
+        }
+        Expr::Literal(Literal::Keyword(keyword)) if keyword.eq_ignore_ascii_case("true") => {
+            Some(model::DefaultValue::I64(1))
+        }
+        Expr::Literal(Literal::Keyword(keyword)) if keyword.eq_ignore_ascii_case("false") => {
+            Some(model::DefaultValue::I64(0))
+        }
+        // the parser gives TRUE and FALSE as identifiers
+        Expr::Id(name) if name.as_str().eq_ignore_ascii_case("true") => {
+            Some(model::DefaultValue::I64(1))
+        }
+        Expr::Id(name) if name.as_str().eq_ignore_ascii_case("false") => {
+            Some(model::DefaultValue::I64(0))
+        }
+        // SQLite takes any other bare identifier as a string
+        Expr::Id(name) => Some(model::DefaultValue::Text(
+            text_encoding.encode(name.as_str()),
+        )),
+        _ => None,
+    }
+}
+
 impl SqlSchema {
+    pub(crate) fn new(
+        columns: Vec<turso_parser::ast::ColumnDefinition>,
+        constraints: Vec<turso_parser::ast::NamedTableConstraint>,
+        without_rowid: bool,
+        text_encoding: model::TextEncoding,
+    ) -> Self {
+        use turso_parser::ast::ColumnConstraint;
+
+        let primary_key = primary_key_columns(&columns, &constraints);
+        let rowid_alias = rowid_alias_column(&columns, &primary_key, without_rowid);
+        let defaults = columns
+            .iter()
+            .map(|col| {
+                col.constraints.iter().find_map(|c| match c.constraint {
+                    ColumnConstraint::Default(ref expr) => Some(default_value(expr, text_encoding)),
+                    _ => None,
+                })?
+            })
+            .collect();
+        SqlSchema {
+            columns,
+            constraints,
+            without_rowid,
+            primary_key,
+            rowid_alias,
+            defaults,
+        }
+    }
+
+    /// DEFAULT of the column at `idx` when it is a constant, `None` otherwise
+    pub fn default_value(&self, idx: usize) -> Option<model::Payload<'_>> {
+        self.defaults
+            .get(idx)?
+            .as_ref()
+            .map(model::DefaultValue::as_payload)
+    }
+
+    /// turn the record of a row into the values of its declared columns
+    ///
+    /// the rowid goes into the INTEGER PRIMARY KEY column, and the columns missing from
+    /// a record written before `ALTER TABLE ADD COLUMN` read their DEFAULT, SQLite does
+    /// not rewrite the table when a column is added
+    pub(crate) fn complete_row<'a>(
+        &'a self,
+        rowid: i64,
+        values: &mut Vec<Option<model::Payload<'a>>>,
+    ) {
+        for idx in values.len()..self.columns.len() {
+            values.push(self.default_value(idx));
+        }
+        if let Some(value) = self.rowid_alias.and_then(|idx| values.get_mut(idx)) {
+            *value = Some(model::Payload::I64(rowid));
+        }
+    }
+
     pub fn get_column_names(&self) -> Vec<String> {
         self.columns
             .iter()
             .map(|col| col.col_name.as_str().to_owned())
             .collect()
     }
+
+    /// declared type of every column, `None` when the column has none
+    pub fn declared_types(&self) -> Vec<Option<&str>> {
+        self.columns
+            .iter()
+            .map(|col| col.col_type.as_ref().map(|col_type| col_type.name.as_str()))
+            .collect()
+    }
+
+    /// type affinity of every column, in declared order
+    pub fn column_affinities(&self) -> Vec<model::Affinity> {
+        self.declared_types()
+            .into_iter()
+            .map(model::Affinity::from_declared_type)
+            .collect()
+    }
+
+    /// for every declared column, its position in the record of a WITHOUT ROWID table
+    ///
+    /// the record stores the PRIMARY KEY columns first, then the rest in declared order
+    pub(crate) fn without_rowid_record_order(&self) -> Vec<usize> {
+        let mut record_columns = self.primary_key.clone();
+        record_columns
+            .extend((0..self.columns.len()).filter(|idx| !self.primary_key.contains(idx)));
+
+        let mut order = vec![0; self.columns.len()];
+        for (record_pos, &column_idx) in record_columns.iter().enumerate() {
+            order[column_idx] = record_pos;
+        }
+        order
+    }
 }
 
-pub struct Reader<S: AsRef<[u8]>> {
-    buf: S,
+pub struct Reader<P: Pager> {
+    pager: P,
     pub header: DbHeader,
+    wal: Option<Wal>,
+    journal: Option<Journal>,
+    /// read the pages saved in `journal` in place of the database file
+    rollback: bool,
     tables: OnceCell<HashMap<String, Option<SqlSchema>>>,
+    catalog: OnceCell<Vec<SchemaObject>>,
 }
 
 impl Reader<Mmap> {
+    /// map the database file, committed frames of `<db>-wal` are read on top of it
+    ///
+    /// a gzip, zstd or xz compressed file is decompressed into a temporary file first, a
+    /// hot `<db>-journal` is only reported, see [`Reader::on_hot_journal`]
     pub fn open_mmap<P: AsRef<Path>>(database: P) -> error::Result<Reader<Mmap>> {
-        let file_read = File::open(database)?;
+        Self::open_mmap_with(database.as_ref(), Reader::from_source)
+    }
+
+    /// like [`Reader::open_mmap`], for a database whose header may be lost, see
+    /// [`Reader::salvage_source`]
+    pub fn salvage_mmap<P: AsRef<Path>>(database: P) -> error::Result<Reader<Mmap>> {
+        Self::open_mmap_with(database.as_ref(), Reader::salvage_source)
+    }
+
+    fn open_mmap_with(
+        database: &Path,
+        from_source: fn(Mmap) -> error::Result<Reader<Mmap>>,
+    ) -> error::Result<Reader<Mmap>> {
+        let file_read = compression::open_decompressed(File::open(database)?)?;
         let mmap = unsafe { MmapOptions::new().map(&file_read) }?;
-        Reader::from_source(mmap)
+        let reader = match Journal::open(journal::journal_path(database))? {
+            Some(journal) => from_source(mmap)?.with_journal(journal, HotJournal::Warn)?,
+            None => from_source(mmap)?,
+        };
+
+        match Wal::open(wal::wal_path(database))? {
+            Some(wal) => reader.with_wal(wal),
+            None => Ok(reader),
+        }
     }
 }
 
-impl<S: AsRef<[u8]> + Sync> Reader<S> {
-    fn from_source(buf: S) -> error::Result<Reader<S>> {
-        let mut input = buf.as_ref();
-        let header = db_header::<ContextError>(&mut input)?;
+impl Reader<PreadPager> {
+    /// read the database file with positioned reads through a cache of `cache_pages` pages
+    ///
+    /// nothing is mapped, committed frames of `<db>-wal` and a hot `<db>-journal` are read
+    /// into memory and a compressed file is decompressed into a temporary file first
+    pub fn open_pread<P: AsRef<Path>>(
+        database: P,
+        cache_pages: usize,
+    ) -> error::Result<Reader<PreadPager>> {
+        Self::open_pread_with(database.as_ref(), cache_pages, Reader::from_source)
+    }
+
+    /// like [`Reader::open_pread`], for a database whose header may be lost, see
+    /// [`Reader::salvage_source`]
+    pub fn salvage_pread<P: AsRef<Path>>(
+        database: P,
+        cache_pages: usize,
+    ) -> error::Result<Reader<PreadPager>> {
+        Self::open_pread_with(database.as_ref(), cache_pages, Reader::salvage_source)
+    }
 
-        let reader = Reader {
-            buf,
+    fn open_pread_with(
+        database: &Path,
+        cache_pages: usize,
+        from_source: fn(PreadPager) -> error::Result<Reader<PreadPager>>,
+    ) -> error::Result<Reader<PreadPager>> {
+        let file = compression::open_decompressed(File::open(database)?)?;
+        let pager = PreadPager::new(file, cache_pages);
+        let reader = match Journal::read(journal::journal_path(database))? {
+            Some(journal) => from_source(pager)?.with_journal(journal, HotJournal::Warn)?,
+            None => from_source(pager)?,
+        };
+
+        match Wal::read(wal::wal_path(database))? {
+            Some(wal) => reader.with_wal(wal),
+            None => Ok(reader),
+        }
+    }
+}
+
+impl Reader<Vec<u8>> {
+    /// read the whole database from `source` into memory, for stdin, pipes and downloads
+    ///
+    /// gzip, zstd and xz compressed data is decompressed in memory
+    pub fn read_from<R: Read>(source: R) -> error::Result<Reader<Vec<u8>>> {
+        Self::read_from_with(source, Reader::from_source)
+    }
+
+    /// like [`Reader::read_from`], for a database whose header may be lost, see
+    /// [`Reader::salvage_source`]
+    pub fn salvage_from<R: Read>(source: R) -> error::Result<Reader<Vec<u8>>> {
+        Self::read_from_with(source, Reader::salvage_source)
+    }
+
+    fn read_from_with<R: Read>(
+        mut source: R,
+        from_source: fn(Vec<u8>) -> error::Result<Reader<Vec<u8>>>,
+    ) -> error::Result<Reader<Vec<u8>>> {
+        let mut buf = Vec::new();
+        source.read_to_end(&mut buf)?;
+        from_source(compression::decompressed(buf)?)
+    }
+}
+
+impl<P: Pager> Reader<P> {
+    /// parse the database behind `pager`, a `Vec<u8>`, a mapped file or any other byte source
+    ///
+    /// no WAL or journal is read, use [`Reader::with_wal`] and [`Reader::with_journal`] to
+    /// add them
+    pub fn from_source(pager: P) -> error::Result<Reader<P>> {
+        let first_page = pager.page(1, HEADER_SIZE)?.ok_or(SQLiteError::Corrupt {
+            page: 1,
+            offset: 0,
+            kind: CorruptionKind::PageOutOfRange,
+        })?;
+        let mut input = &*first_page;
+        let header =
+            db_header::<ParseFailure>(&mut input).map_err(|err| err.at_page(1, &first_page, 0))?;
+        drop(first_page);
+
+        Ok(Reader::new(pager, header))
+    }
+
+    fn new(pager: P, header: DbHeader) -> Reader<P> {
+        Reader {
+            pager,
             header,
+            wal: None,
+            journal: None,
+            rollback: false,
             tables: OnceCell::default(),
+            catalog: OnceCell::default(),
+        }
+    }
+
+    /// overlay the committed frames of `wal` on top of the database pages
+    ///
+    /// a WAL written for another page size is ignored, like SQLite does
+    pub fn with_wal(mut self, wal: Wal) -> error::Result<Self> {
+        if wal.header.page_size as usize != self.header.page_size.real_size() {
+            return Ok(self);
+        }
+
+        // page 1 may have been rewritten in the WAL, and the header with it
+        if let Some(first_page) = wal.page(1) {
+            let mut input = first_page;
+            self.header = db_header::<ParseFailure>(&mut input)
+                .map_err(|err| err.at_page(1, first_page, 0))?;
+        }
+
+        self.wal = Some(wal);
+        self.tables = OnceCell::default();
+        self.catalog = OnceCell::default();
+        Ok(self)
+    }
+
+    pub fn wal(&self) -> Option<&Wal> {
+        self.wal.as_ref()
+    }
+
+    /// keep `journal` as the hot journal of the database and deal with it as `policy` says
+    pub fn with_journal(mut self, journal: Journal, policy: HotJournal) -> error::Result<Self> {
+        self.journal = Some(journal);
+        self.on_hot_journal(policy)
+    }
+
+    /// deal with the hot journal of the database as `policy` says, nothing happens without one
+    ///
+    /// a reader opened by [`Reader::open_mmap`] or [`Reader::open_pread`] starts with
+    /// [`HotJournal::Warn`], a rolled back reader goes back to the file with it
+    pub fn on_hot_journal(mut self, policy: HotJournal) -> error::Result<Self> {
+        let Some(journal) = &self.journal else {
+            return Ok(self);
         };
 
-        Ok(reader)
+        let rollback = match policy {
+            HotJournal::Warn => false,
+            HotJournal::Refuse => return Err(SQLiteError::HotJournal(journal.record_count())),
+            HotJournal::Rollback => {
+                let page_size = self.header.page_size.real_size();
+                if journal.header.page_size as usize != page_size {
+                    return Err(SQLiteError::Other(format!(
+                        "journal holds {} byte pages, the database {} byte pages",
+                        journal.header.page_size, page_size
+                    )));
+                }
+                true
+            }
+        };
+
+        if rollback != self.rollback {
+            self.rollback = rollback;
+            // page 1 may be saved in the journal, and the header with it
+            self.reload_header()?;
+        }
+        Ok(self)
     }
 
-    fn get_page(&self, pageno: u32) -> error::Result<Page<'_>> {
-        use crate::parser::page_with_overflow;
+    /// read the database as it was right after commit `commit` of its WAL, numbered as in
+    /// [`Wal::commits`], 0 reads the database file as it was before the first one
+    ///
+    /// the reader can move back and forth between commits, two readers of the database at
+    /// different commits are compared with [`Reader::diff_table`]
+    pub fn at_commit(mut self, commit: usize) -> error::Result<Self> {
+        let commits = self.wal.as_ref().map_or(0, |wal| wal.commits().len());
+        if commit > commits {
+            return Err(SQLiteError::Other(format!(
+                "the WAL holds {} commits, there is no commit {}",
+                commits, commit
+            )));
+        }
+
+        if let Some(wal) = self.wal.as_mut().filter(|wal| wal.commit() != commit) {
+            wal.read_at(commit);
+            // page 1 may be written by the commit or not yet, and the header with it
+            self.reload_header()?;
+        }
+        Ok(self)
+    }
+
+    /// parse the header again after the pages it is read from changed, the schema goes
+    /// with it
+    fn reload_header(&mut self) -> error::Result<()> {
+        let first_page = self.page_bytes(1)?;
+        let mut input = &*first_page;
+        let header =
+            db_header::<ParseFailure>(&mut input).map_err(|err| err.at_page(1, &first_page, 0))?;
+        drop(first_page);
+
+        self.header = header;
+        self.tables = OnceCell::default();
+        self.catalog = OnceCell::default();
+        Ok(())
+    }
+
+    /// rollback journal left next to the database by a writer that didn't finish, whether
+    /// or not it is rolled back
+    pub fn hot_journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// raw bytes of a whole page, the newest committed WAL frame wins over the database file,
+    /// and so does the journal when it is rolled back
+    #[inline(always)]
+    fn page_bytes(&self, pageno: u32) -> error::Result<PageRef<'_>> {
+        // pages are numbered from 1, a 0 is a corrupt pointer or the root page of a view
+        if pageno == 0 {
+            return Err(SQLiteError::Corrupt {
+                page: pageno,
+                offset: 0,
+                kind: CorruptionKind::PageOutOfRange,
+            });
+        }
+
+        if let Some(page) = self.wal.as_ref().and_then(|wal| wal.page(pageno)) {
+            return Ok(PageRef::Borrowed(page));
+        }
 
         let page_size = self.header.page_size.real_size();
+        let out_of_range = SQLiteError::Corrupt {
+            page: pageno,
+            offset: page_size * (pageno as usize - 1),
+            kind: CorruptionKind::PageOutOfRange,
+        };
 
-        let pageno_usize = (pageno as usize).saturating_sub(1);
+        if let Some(journal) = self.journal.as_ref().filter(|_| self.rollback) {
+            // the pages the transaction appended go away with it, like SQLite truncates the
+            // file on rollback
+            if pageno > journal.db_size() {
+                return Err(out_of_range);
+            }
+            if let Some(page) = journal.page(pageno) {
+                return Ok(PageRef::Borrowed(page));
+            }
+        }
+
+        self.pager.page(pageno, page_size)?.ok_or(out_of_range)
+    }
 
-        let page_bytes =
-            &self.buf.as_ref()[page_size * pageno_usize..page_size * (pageno_usize + 1)];
+    /// parse page `pageno` from its bytes
+    fn parse_page<'p>(&self, pageno: u32, page_bytes: &'p [u8]) -> error::Result<Page<'p>> {
+        use crate::parser::page_with_overflow;
 
         let page_start_offset = if pageno <= 1 { HEADER_SIZE } else { 0 };
         let input_bytes = if pageno <= 1 {
@@ -120,40 +727,67 @@ impl<S: AsRef<[u8]> + Sync> Reader<S> {
         };
 
         let mut input = input_bytes;
-        let page = page_with_overflow::<ContextError>(&mut input, &self.header, page_start_offset)?;
+        let page = page_with_overflow::<ParseFailure>(&mut input, &self.header, page_start_offset)
+            .map_err(|err| err.at_page(pageno, input_bytes, page_start_offset))?;
 
         Ok(page)
     }
 
-    fn get_overflow_page(&self, pageno: u32) -> Result<model::OverflowPage<'_>, SQLiteError> {
-        let page_size = self.header.page_size.real_size();
-        let usable_size = self.header.usable_page_size();
-
-        let pageno_usize = (pageno as usize).saturating_sub(1);
-
-        let page_start = page_size * pageno_usize;
-        let page_bytes = &self.buf.as_ref()[page_start..page_start + usable_size];
-
-        let mut input = page_bytes;
-        let overflow = overflow_page::<ContextError>(&mut input)?;
-
-        Ok(overflow)
-    }
-
-    fn read_overflow_chain(&self, first_page: u32, total_size: usize) -> error::Result<Vec<u8>> {
-        let mut buffer = Vec::with_capacity(total_size);
+    /// append the overflow chain starting at `first_page` to `buffer` until it holds `total_size` bytes
+    fn read_overflow_chain(
+        &self,
+        first_page: u32,
+        buffer: &mut Vec<u8>,
+        total_size: usize,
+    ) -> error::Result<()> {
         let mut next_page = Some(first_page);
+        let mut visited = HashSet::default();
 
-        while buffer.len() < total_size && next_page.is_some() {
-            let (next_page_no, payload) = self.get_overflow_page(next_page.unwrap())?;
+        while let Some(pageno) = next_page.filter(|_| buffer.len() < total_size) {
+            if !visited.insert(pageno) {
+                return Err(SQLiteError::Corrupt {
+                    page: pageno,
+                    offset: 0,
+                    kind: CorruptionKind::PageCycle,
+                });
+            }
+
+            let page_bytes = self.page_bytes(pageno)?;
+            let page_bytes = &page_bytes[..self.header.usable_page_size()];
+            let mut input = page_bytes;
+            let (next_page_no, payload) = overflow_page::<ParseFailure>(&mut input)
+                .map_err(|err| err.at_page(pageno, page_bytes, 0))?;
             let to_read = (total_size - buffer.len()).min(payload.len());
             buffer.extend_from_slice(&payload[..to_read]);
             next_page = next_page_no;
         }
 
+        Ok(())
+    }
+
+    /// join the local part of a payload with its overflow chain
+    fn join_overflow(
+        &self,
+        local_payload: &[u8],
+        first_page: u32,
+        payload_size: u64,
+    ) -> error::Result<Vec<u8>> {
+        // SQLite never writes a payload larger than this, a bigger size is a corrupt cell
+        if payload_size > i32::MAX as u64 {
+            return Err(SQLiteError::Corrupt {
+                page: first_page,
+                offset: 0,
+                kind: CorruptionKind::Malformed,
+            });
+        }
+
+        let mut buffer = Vec::with_capacity(payload_size as usize);
+        buffer.extend_from_slice(local_payload);
+        self.read_overflow_chain(first_page, &mut buffer, payload_size as usize)?;
         Ok(buffer)
     }
 
+    /// the whole record of a cell: its local payload followed by the overflow chain
     #[inline(always)]
     pub fn reconstruct_full_payload(
         &self,
@@ -166,213 +800,333 @@ impl<S: AsRef<[u8]> + Sync> Reader<S> {
         }
 
         let overflow_page_no = cell.overflow_page_no.unwrap();
-        let overflow_data =
-            self.read_overflow_chain(overflow_page_no, cell.payload_size as usize)?;
-
-        Ok(overflow_data)
+        self.join_overflow(cell.local_payload, overflow_page_no, cell.payload_size)
     }
 
+    /// every table in sqlite_schema, with its parsed CREATE TABLE when the parser accepts it,
+    /// virtual tables have no pages and are only in [`Reader::catalog`]
     pub fn get_tables_map(&self) -> error::Result<&HashMap<String, Option<SqlSchema>>> {
         self.tables.get_or_try_init(|| {
-            let root = self.get_page(0)?;
-            let mut new_tables = HashMap::default();
-
-            match root {
-                Page::LeafTable(ref p) => {
-                    self.extract_tables_from_leaf(&p.cells, &mut new_tables);
-                }
-                Page::InteriorTable(ref p) => {
-                    // for interior page, only read the sqlite_master leaf pages
-                    // never recursively traverse, those would be user table pages
-                    let _ = self.traverse_interior_children(&p.header, &p.cells, |reader, page| {
-                        if let Page::LeafTable(ref leaf) = page {
-                            reader.extract_tables_from_leaf(&leaf.cells, &mut new_tables);
-                        }
-                        Ok::<Option<()>, SQLiteError>(None)
-                    });
-                }
-                _ => {}
-            }
-            Ok(new_tables)
+            let tables = self
+                .catalog()?
+                .iter()
+                .filter(|object| object.kind == ObjectKind::Table)
+                .map(|object| (object.name.clone(), object.table_schema().cloned()))
+                .collect();
+            Ok(tables)
         })
     }
 
-    #[inline(always)]
-    fn extract_tables_from_column_values<'a>(
-        &self,
-        column_values: &[Option<model::Payload<'a>>],
-        tables: &mut HashMap<String, Option<SqlSchema>>,
-    ) {
-        if column_values.len() == SQLITE_MASTER_TABLE_SIZE {
-            if let Some(model::Payload::Text(ref type_text)) =
-                column_values[SqliteMasterTable::Type as usize]
-            {
-                let type_str = type_text.decode(self.header.db_text_encoding);
-                if type_str == "table" {
-                    if let Some(model::Payload::Text(ref name_text)) =
-                        column_values[SqliteMasterTable::Name as usize]
-                    {
-                        let table_name =
-                            name_text.decode(self.header.db_text_encoding).into_owned();
-
-                        let table_schema = match column_values[SqliteMasterTable::Sql as usize] {
-                            Some(model::Payload::Text(ref sql_text)) => SqlSchema::try_from(
-                                sql_text.decode(self.header.db_text_encoding).into_owned(),
-                            )
-                            .ok(),
-                            _ => None,
-                        };
-
-                        tables.insert(table_name.clone(), table_schema);
-                    }
-                }
-            }
-        }
-    }
-
-    #[inline(always)]
-    fn extract_tables_from_leaf<'a>(
+    pub fn stream_table_rows_sequential<F>(
         &self,
-        cells: &[model::LeafTableCell<'a>],
-        tables: &mut HashMap<String, Option<SqlSchema>>,
-    ) {
-        for cell in cells {
-            let column_values = get_table_cell_values(cell);
-            self.extract_tables_from_column_values(column_values, tables);
-        }
+        table_name: &str,
+        callback: F,
+    ) -> error::Result<()>
+    where
+        F: FnMut(&model::LeafTableCell<'_>, &Vec<Option<model::Payload<'_>>>) -> error::Result<()>,
+    {
+        self.stream_table_rows_range(table_name, .., callback)
     }
 
-    pub fn stream_table_rows_sequential<F>(
+    /// stream the rows of `table_name` whose rowid is in `rowids`, in rowid order
+    ///
+    /// the keys of the interior pages are used to only descend into subtrees that can hold
+    /// a matching row
+    pub fn stream_table_rows_range<F>(
         &self,
         table_name: &str,
+        rowids: impl RangeBounds<i64>,
         mut callback: F,
     ) -> error::Result<()>
     where
         F: FnMut(&model::LeafTableCell<'_>, &Vec<Option<model::Payload<'_>>>) -> error::Result<()>,
     {
-        let root = self.get_page(0)?;
-
-        let table_root_pageno = match root {
-            Page::LeafTable(ref p) => self.find_table_root_in_leaf(&p.cells, table_name)?,
-            Page::InteriorTable(ref p) => self.find_table_root_in_interior(p, table_name)?,
-            _ => None,
-        };
-
-        let table_root_pageno = table_root_pageno
-            .ok_or_else(|| SQLiteError::Other(format!("Table '{}' not found", table_name)))?;
-
+        let table_root_pageno = self.rowid_table_root(table_name)?;
+        let rowids = (rowids.start_bound().cloned(), rowids.end_bound().cloned());
         let mut cached_types = HashMap::default();
 
-        self.stream_table_rows_from_page(table_root_pageno, &mut callback, &mut cached_types)
+        self.stream_table_rows_from_page(
+            table_root_pageno,
+            &rowids,
+            self.table_schema(table_name)?,
+            &mut callback,
+            &mut cached_types,
+            &mut TreeWalk::default(),
+        )
     }
 
-    #[inline(always)]
-    fn find_table_root_in_column_values<'a>(
-        &self,
-        column_values: &[Option<model::Payload<'a>>],
-        table_name: &str,
-    ) -> error::Result<Option<u32>> {
-        if column_values.len() == SQLITE_MASTER_TABLE_SIZE {
-            if let Some(model::Payload::Text(ref type_text)) =
-                column_values[SqliteMasterTable::Type as usize]
-            {
-                let type_str = type_text.decode(self.header.db_text_encoding);
-                if type_str == "table" {
-                    if let Some(model::Payload::Text(ref name_text)) =
-                        column_values[SqliteMasterTable::Name as usize]
-                    {
-                        let name = name_text.decode(self.header.db_text_encoding);
-                        if name == table_name {
-                            if let Some(ref pageno_payload) =
-                                column_values[SqliteMasterTable::RootPage as usize]
-                            {
-                                if let Some(pageno) = pageno_payload.as_u32() {
-                                    return Ok(Some(pageno));
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        Ok(None)
+    /// parsed CREATE TABLE of `table_name`, `None` when the parser rejects it
+    fn table_schema(&self, table_name: &str) -> error::Result<Option<&SqlSchema>> {
+        Ok(self
+            .find_object(ObjectKind::Table, table_name)?
+            .and_then(SchemaObject::table_schema))
     }
 
-    #[inline(always)]
-    fn find_table_root_in_leaf<'a>(
-        &self,
-        cells: &[model::LeafTableCell<'a>],
-        table_name: &str,
-    ) -> error::Result<Option<u32>> {
-        for cell in cells {
-            let column_values = get_table_cell_values(cell);
-            if let Some(pageno) =
-                self.find_table_root_in_column_values(column_values, table_name)?
-            {
-                return Ok(Some(pageno));
+    /// root page of `table_name`, which has to be a table with a rowid
+    fn rowid_table_root(&self, table_name: &str) -> error::Result<u32> {
+        if let Some(schema) = self.table_schema(table_name)? {
+            if schema.without_rowid {
+                return Err(SQLiteError::Other(format!(
+                    "Table '{}' is a WITHOUT ROWID table, use stream_without_rowid_rows",
+                    table_name
+                )));
             }
         }
-        Ok(None)
+
+        self.find_root_page(ObjectKind::Table, table_name)?
+            .ok_or_else(|| SQLiteError::Other(format!("Table '{}' not found", table_name)))
     }
 
-    fn traverse_interior_children<F, R>(
-        &self,
-        header: &model::InteriorPageHeader,
-        cells: &[model::InteriorCell],
-        mut visitor: F,
-    ) -> error::Result<Option<R>>
+    /// run `f` on the row of `table_name` with the given `rowid`, `None` if there is no such row
+    pub fn get_row<F, R>(&self, table_name: &str, rowid: i64, f: F) -> error::Result<Option<R>>
     where
-        F: FnMut(&Self, &Page) -> error::Result<Option<R>>,
+        F: FnOnce(&model::LeafTableCell<'_>, &[Option<model::Payload<'_>>]) -> error::Result<R>,
     {
-        for cell in cells {
-            let page = self.get_page(cell.left_child_page_no)?;
-            if let Some(result) = visitor(self, &page)? {
-                return Ok(Some(result));
-            }
-        }
+        let mut f = Some(f);
+        let mut result = None;
 
-        if header.rightmost_pointer > 0 {
-            let page = self.get_page(header.rightmost_pointer)?;
-            if let Some(result) = visitor(self, &page)? {
-                return Ok(Some(result));
+        self.stream_table_rows_range(table_name, rowid..=rowid, |cell, column_values| {
+            if let Some(f) = f.take() {
+                result = Some(f(cell, column_values)?);
             }
-        }
+            Ok(())
+        })?;
+
+        Ok(result)
+    }
+
+    /// root page of the sqlite_schema entry of the given kind and `name`
+    ///
+    /// objects without a b-tree of their own have no root page
+    fn find_root_page(&self, kind: ObjectKind, name: &str) -> error::Result<Option<u32>> {
+        Ok(self
+            .find_object(kind, name)?
+            .map(|object| object.root_page)
+            .filter(|&root_page| root_page > 0))
+    }
 
-        Ok(None)
+    /// the sqlite_schema entry of the given kind and `name`, names are compared
+    /// case-insensitively like SQLite does
+    fn find_object(&self, kind: ObjectKind, name: &str) -> error::Result<Option<&SchemaObject>> {
+        Ok(self
+            .catalog()?
+            .iter()
+            .find(|object| object.kind == kind && object.name.eq_ignore_ascii_case(name)))
     }
 
-    fn find_table_root_in_interior(
+    /// stream every entry of the index `index_name` in key order
+    ///
+    /// each entry is the decoded index record: the key columns followed by the rowid
+    /// of the table row (or the primary key columns for a WITHOUT ROWID table)
+    pub fn stream_index_entries<F>(&self, index_name: &str, mut callback: F) -> error::Result<()>
+    where
+        F: FnMut(&[Option<model::Payload<'_>>]) -> error::Result<()>,
+    {
+        let index_root_pageno = self
+            .find_root_page(ObjectKind::Index, index_name)?
+            .ok_or_else(|| SQLiteError::Other(format!("Index '{}' not found", index_name)))?;
+
+        self.stream_index_entries_from_page(
+            index_root_pageno,
+            None,
+            &mut callback,
+            &mut TreeWalk::default(),
+        )
+    }
+
+    /// stream the rows of a WITHOUT ROWID table in PRIMARY KEY order
+    ///
+    /// the values are in declared column order, there is no rowid
+    pub fn stream_without_rowid_rows<F>(
         &self,
-        interior: &model::InteriorTablePage,
         table_name: &str,
-    ) -> error::Result<Option<u32>> {
-        self.traverse_interior_children(
-            &interior.header,
-            &interior.cells,
-            |reader, page| match page {
-                Page::LeafTable(ref p) => reader.find_table_root_in_leaf(&p.cells, table_name),
-                Page::InteriorTable(ref p) => reader.find_table_root_in_interior(p, table_name),
-                _ => Ok(None),
+        mut callback: F,
+    ) -> error::Result<()>
+    where
+        F: FnMut(&[Option<model::Payload<'_>>]) -> error::Result<()>,
+    {
+        let schema = match self.find_object(ObjectKind::Table, table_name)? {
+            Some(object) => match object.table_schema() {
+                Some(schema) if schema.without_rowid => schema,
+                _ => {
+                    return Err(SQLiteError::Other(format!(
+                        "Table '{}' is not a WITHOUT ROWID table",
+                        table_name
+                    )))
+                }
             },
+            None => return Err(SQLiteError::TableNotFound(table_name.to_owned())),
+        };
+
+        let table_root_pageno = self
+            .find_root_page(ObjectKind::Table, table_name)?
+            .ok_or_else(|| SQLiteError::TableNotFound(table_name.to_owned()))?;
+
+        let order = schema.without_rowid_record_order();
+        self.stream_index_entries_from_page(
+            table_root_pageno,
+            Some((&order, schema)),
+            &mut callback,
+            &mut TreeWalk::default(),
         )
     }
 
-    fn stream_table_rows_from_page<F>(
+    fn stream_index_entries_from_page<F>(
         &self,
         pageno: u32,
+        order: Option<(&[usize], &SqlSchema)>,
+        callback: &mut F,
+        walk: &mut TreeWalk,
+    ) -> error::Result<()>
+    where
+        F: FnMut(&[Option<model::Payload<'_>>]) -> error::Result<()>,
+    {
+        walk.enter(pageno)?;
+        let page_bytes = self.page_bytes(pageno)?;
+        let mut column_values = Vec::new();
+        let mut row = Vec::new();
+
+        let result = match self.parse_page(pageno, &page_bytes)? {
+            Page::InteriorIndex(ref p) => {
+                for cell in &p.cells {
+                    self.stream_index_entries_from_page(
+                        cell.left_child_page_no,
+                        order,
+                        callback,
+                        walk,
+                    )?;
+                    self.index_cell_values(
+                        (pageno, &page_bytes),
+                        &cell.cell,
+                        order,
+                        callback,
+                        &mut column_values,
+                        &mut row,
+                    )?;
+                }
+                self.stream_index_entries_from_page(
+                    p.header.rightmost_pointer,
+                    order,
+                    callback,
+                    walk,
+                )
+            }
+            Page::LeafIndex(ref p) => {
+                for cell in &p.cells {
+                    self.index_cell_values(
+                        (pageno, &page_bytes),
+                        cell,
+                        order,
+                        callback,
+                        &mut column_values,
+                        &mut row,
+                    )?;
+                }
+                Ok(())
+            }
+            // a table page in an index b-tree
+            _ => {
+                let page_start_offset = if pageno <= 1 { HEADER_SIZE } else { 0 };
+                Err(SQLiteError::Corrupt {
+                    page: pageno,
+                    offset: page_start_offset,
+                    kind: CorruptionKind::InvalidPageType(page_bytes[page_start_offset]),
+                })
+            }
+        };
+        walk.leave();
+        result
+    }
+
+    /// decode an index cell and hand it to `callback`, reordered by `order` when given
+    ///
+    /// columns the record lacks read the DEFAULT of the table, `page` is the number and
+    /// bytes of the page holding `cell`
+    #[inline(always)]
+    fn index_cell_values<'a, F>(
+        &self,
+        (pageno, page_bytes): (u32, &[u8]),
+        cell: &model::IndexCell<'a>,
+        order: Option<(&[usize], &'a SqlSchema)>,
+        callback: &mut F,
+        column_values: &mut Vec<Option<model::Payload<'a>>>,
+        row: &mut Vec<Option<model::Payload<'a>>>,
+    ) -> error::Result<()>
+    where
+        F: FnMut(&[Option<model::Payload<'_>>]) -> error::Result<()>,
+    {
+        use crate::parser::record;
+
+        #[inline(always)]
+        fn reorder<'a>(
+            values: &[Option<model::Payload<'a>>],
+            (order, schema): (&[usize], &'a SqlSchema),
+            row: &mut Vec<Option<model::Payload<'a>>>,
+        ) {
+            row.clear();
+            row.extend(
+                order
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, &pos)| match values.get(pos) {
+                        Some(value) => value.clone(),
+                        None => schema.default_value(idx),
+                    }),
+            );
+        }
+
+        match cell.overflow_page_no {
+            None => {
+                let mut input = cell.local_payload;
+                record::<ParseFailure>(&mut input, column_values).map_err(|err| {
+                    corrupt_record(pageno, page_bytes, cell.local_payload, err.kind)
+                })?;
+                match order {
+                    Some(order) => {
+                        reorder(column_values, order, row);
+                        callback(row)
+                    }
+                    None => callback(column_values),
+                }
+            }
+            Some(overflow_page_no) => {
+                let full_payload =
+                    self.join_overflow(cell.local_payload, overflow_page_no, cell.payload_size)?;
+                let mut input = full_payload.as_slice();
+                let mut overflow_values = Vec::new();
+                record::<ParseFailure>(&mut input, &mut overflow_values).map_err(|err| {
+                    corrupt_record(pageno, page_bytes, cell.local_payload, err.kind)
+                })?;
+                match order {
+                    Some(order) => {
+                        let mut overflow_row = Vec::with_capacity(order.0.len());
+                        reorder(&overflow_values, order, &mut overflow_row);
+                        callback(&overflow_row)
+                    }
+                    None => callback(&overflow_values),
+                }
+            }
+        }
+    }
+
+    /// `schema` completes the rows of a table, sqlite_schema has none
+    fn stream_table_rows_from_page<'r, F>(
+        &'r self,
+        pageno: u32,
+        rowids: &(Bound<i64>, Bound<i64>),
+        schema: Option<&'r SqlSchema>,
         callback: &mut F,
         cached_types: &mut HashMap<u64, std::sync::Arc<Vec<model::SerialType>>>,
+        walk: &mut TreeWalk,
     ) -> error::Result<()>
     where
         F: FnMut(&model::LeafTableCell<'_>, &Vec<Option<model::Payload<'_>>>) -> error::Result<()>,
     {
         use crate::parser::stream_page_cells;
 
-        let page_size = self.header.page_size.real_size();
-        let pageno_usize = (pageno as usize).saturating_sub(1);
-
-        let page_bytes =
-            &self.buf.as_ref()[page_size * pageno_usize..page_size * (pageno_usize + 1)];
+        walk.enter(pageno)?;
+        let page_bytes = self.page_bytes(pageno)?;
+        let page_bytes = &*page_bytes;
 
         let page_start_offset = if pageno <= 1 { HEADER_SIZE } else { 0 };
         let input_bytes = if pageno <= 1 {
@@ -381,29 +1135,70 @@ impl<S: AsRef<[u8]> + Sync> Reader<S> {
             page_bytes
         };
         let mut column_values = Vec::new();
+        // key of the previous interior cell, the next child only holds rowids above it
+        let mut previous_key = None;
 
-        stream_page_cells(
+        let result = stream_page_cells(
             input_bytes,
+            pageno,
             &self.header,
             page_start_offset,
             &mut column_values,
             &mut *cached_types,
             |cell_type, cache| {
                 match cell_type {
-                    parser::CellType::LeafTable(cell, column_values) => {
+                    parser::CellType::LeafTable(cell, _)
+                        if !rowids.contains(&(cell.rowid as i64)) =>
+                    {
+                        Ok(())
+                    }
+                    parser::CellType::LeafTable(cell, column_values)
+                        if cell.overflow_page_no.is_none() =>
+                    {
+                        if let Some(schema) = schema {
+                            schema.complete_row(cell.rowid as i64, column_values);
+                        }
                         callback(&cell, column_values)
                     }
+                    parser::CellType::LeafTable(mut cell, _) => {
+                        let full_payload = self.reconstruct_full_payload(&cell)?;
+                        let mut input = full_payload.as_slice();
+                        let mut overflow_values = Vec::new();
+                        let types =
+                            parser::record::<ParseFailure>(&mut input, &mut overflow_values)
+                                .map_err(|err| {
+                                    corrupt_record(pageno, page_bytes, cell.local_payload, err.kind)
+                                })?;
+                        cell.payload.column_types = std::sync::Arc::new(types);
+                        if let Some(schema) = schema {
+                            schema.complete_row(cell.rowid as i64, &mut overflow_values);
+                        }
+                        callback(&cell, &overflow_values)
+                    }
                     // parser::CellType::LeafIndex => {
                     //     Ok(())
                     // }
-                    parser::CellType::InteriorTable(pageno) => {
-                        self.stream_table_rows_from_page(pageno, callback, cache)
+                    parser::CellType::InteriorTable(pageno, key) => {
+                        let after = previous_key.replace(key);
+                        if !subtree_in_range(rowids, after, Some(key)) {
+                            return Ok(());
+                        }
+                        self.stream_table_rows_from_page(
+                            pageno, rowids, schema, callback, cache, walk,
+                        )
                     }
                     parser::CellType::InteriorTableRightmost(pageno) => {
-                        self.stream_table_rows_from_page(pageno, callback, cache)
+                        if !subtree_in_range(rowids, previous_key, None) {
+                            return Ok(());
+                        }
+                        self.stream_table_rows_from_page(
+                            pageno, rowids, schema, callback, cache, walk,
+                        )
                     }
                 }
             },
-        )
+        );
+        walk.leave();
+        result
     }
 }
@@ -4,38 +4,120 @@ use std::borrow::Cow;
 
 const SIMD_CHUNK_SIZE: usize = 64;
 
+/// the 100-byte header at the start of page 1
+#[derive(Debug, Clone)]
 pub struct DbHeader {
     pub page_size: PageSize,
-    // pub(crate) write_version: u8,
-    // pub(crate) read_version: u8,
+    pub write_version: FileFormatVersion,
+    pub read_version: FileFormatVersion,
     /// number of bytes reserved at the end of each page (usually 0)
-    pub(crate) reserved_size: u8,
-    // pub(crate) max_payload_fraction: u8,
-    // pub(crate) min_payload_fraction: u8,
-    // pub(crate) leaf_payload_fraction: u8,
-    // pub(crate) file_change_counter: u32,
-    // pub(crate) db_size: u32,
-    // pub(crate) first_freelist_page_no: u32,
-    // pub(crate) total_freelist_pages: u32,
-    // pub(crate) schema_cookie: u32,
-    // pub(crate) schema_format_no: u32,
-    // pub(crate) default_page_cache_size: u32,
-    // pub(crate) no_largest_root_b_tree: u32,
+    pub reserved_size: u8,
+    /// maximum embedded payload fraction, must be 64
+    pub max_payload_fraction: u8,
+    /// minimum embedded payload fraction, must be 32
+    pub min_payload_fraction: u8,
+    /// leaf payload fraction, must be 32
+    pub leaf_payload_fraction: u8,
+    pub file_change_counter: u32,
+    /// size of the database in pages, only trusted when [`DbHeader::in_header_db_size`] says so
+    pub db_size: u32,
+    /// page number of the first freelist trunk page, 0 if the freelist is empty
+    pub first_freelist_page_no: u32,
+    pub total_freelist_pages: u32,
+    pub schema_cookie: u32,
+    /// schema format number, 1 to 4
+    pub schema_format_no: u32,
+    pub default_page_cache_size: u32,
+    /// page number of the largest root b-tree page in auto-vacuum modes, 0 otherwise
+    pub no_largest_root_b_tree: u32,
     pub db_text_encoding: TextEncoding,
-    // pub(crate) user_version: u32,
-    // pub(crate) incremental_vacuum_mode: u32,
-    // pub(crate) application_id: u32,
-    // pub(crate) version_valid_for_no: u32,
-    // pub(crate) sqlite_version_number: u32,
+    pub user_version: u32,
+    pub incremental_vacuum_mode: bool,
+    pub application_id: u32,
+    /// value of the change counter when `sqlite_version_number` was stored
+    pub version_valid_for_no: u32,
+    /// `SQLITE_VERSION_NUMBER` of the library that last wrote the file, e.g. 3040001
+    pub sqlite_version_number: u32,
 }
 
 impl DbHeader {
+    /// a header for a database whose own header is lost, with pages of `page_size` bytes
+    /// and the defaults of SQLite for everything else
+    pub(crate) fn with_page_size(page_size: usize) -> DbHeader {
+        DbHeader {
+            page_size: PageSize(if page_size == 0x1_00_00 {
+                1
+            } else {
+                page_size as u16
+            }),
+            write_version: FileFormatVersion::Legacy,
+            read_version: FileFormatVersion::Legacy,
+            reserved_size: 0,
+            max_payload_fraction: 64,
+            min_payload_fraction: 32,
+            leaf_payload_fraction: 32,
+            file_change_counter: 0,
+            db_size: 0,
+            first_freelist_page_no: 0,
+            total_freelist_pages: 0,
+            schema_cookie: 0,
+            schema_format_no: 4,
+            default_page_cache_size: 0,
+            no_largest_root_b_tree: 0,
+            db_text_encoding: TextEncoding::Utf8,
+            user_version: 0,
+            incremental_vacuum_mode: false,
+            application_id: 0,
+            version_valid_for_no: 0,
+            sqlite_version_number: 0,
+        }
+    }
+
     /// calculate the usable page size
     pub(crate) fn usable_page_size(&self) -> usize {
         self.page_size.real_size() - (self.reserved_size as usize)
     }
+
+    /// calculate local and overflow payload sizes for an index b-tree cell
+    pub(crate) fn index_local_and_overflow_size(
+        &self,
+        payload_size: u64,
+    ) -> (usize, Option<usize>) {
+        let usable = self.usable_page_size();
+        let max_local = ((usable - 12) * 64 / 255) - 23;
+        local_and_overflow_size(usable, max_local, payload_size)
+    }
+
+    /// the in-header database size, if the file was last written by a SQLite that keeps it valid
+    pub fn in_header_db_size(&self) -> Option<u32> {
+        if self.db_size != 0 && self.version_valid_for_no == self.file_change_counter {
+            Some(self.db_size)
+        } else {
+            None
+        }
+    }
+}
+
+/// file format read/write version
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileFormatVersion {
+    /// rollback journal
+    Legacy,
+    Wal,
+    Unknown(u8),
 }
 
+impl From<u8> for FileFormatVersion {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => FileFormatVersion::Legacy,
+            2 => FileFormatVersion::Wal,
+            n => FileFormatVersion::Unknown(n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct PageSize(pub(crate) u16);
 
 impl PageSize {
@@ -46,6 +128,12 @@ impl PageSize {
             _ => self.0.into(),
         }
     }
+
+    /// a power of two between 512 and 65536
+    pub(crate) fn is_valid(&self) -> bool {
+        let size = self.real_size();
+        size.is_power_of_two() && (512..=0x1_00_00).contains(&size)
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -62,7 +150,9 @@ impl TryFrom<u32> for TextEncoding {
         use TextEncoding::*;
 
         match value {
-            1 => Ok(Utf8),
+            // a database without any schema yet, like the main file of a WAL database
+            // that was never checkpointed, SQLite reads it as UTF-8
+            0 | 1 => Ok(Utf8),
             2 => Ok(Utf16Le),
             3 => Ok(Utf16Be),
             _ => Err(SQLiteError::UnknownTextEncodingError(value)),
@@ -70,9 +160,56 @@ impl TryFrom<u32> for TextEncoding {
     }
 }
 
+impl TextEncoding {
+    /// `text` as it is stored in a database with this encoding
+    pub(crate) fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            TextEncoding::Utf8 => text.as_bytes().to_vec(),
+            TextEncoding::Utf16Le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            TextEncoding::Utf16Be => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        }
+    }
+}
+
+/// type affinity of a column, what SQLite converts stored values towards
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Affinity {
+    Integer,
+    Text,
+    Blob,
+    Real,
+    Numeric,
+}
+
+impl Affinity {
+    /// affinity of a declared column type, by the rules of "Determination Of Column Affinity"
+    ///
+    /// the rules are tried in order and match substrings, so `CHARINT` is INTEGER and
+    /// `FLOATING POINT` is INTEGER too, a column without a type has BLOB affinity
+    pub fn from_declared_type(declared_type: Option<&str>) -> Self {
+        let Some(declared_type) = declared_type else {
+            return Affinity::Blob;
+        };
+        let declared_type = declared_type.to_ascii_uppercase();
+        let contains = |pattern| declared_type.contains(pattern);
+
+        if contains("INT") {
+            Affinity::Integer
+        } else if contains("CHAR") || contains("CLOB") || contains("TEXT") {
+            Affinity::Text
+        } else if contains("BLOB") || declared_type.is_empty() {
+            Affinity::Blob
+        } else if contains("REAL") || contains("FLOA") || contains("DOUB") {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+}
+
 pub(crate) enum Page<'a> {
-    InteriorIndex,
-    LeafIndex,
+    InteriorIndex(InteriorIndexPage<'a>),
+    LeafIndex(LeafIndexPage<'a>),
     InteriorTable(InteriorTablePage),
     LeafTable(LeafTablePage<'a>),
     // Overflow(OverflowPage<'a>),
@@ -94,12 +231,16 @@ pub(crate) struct InteriorTablePage {
 
 pub(crate) struct InteriorCell {
     pub(crate) left_child_page_no: u32,
+    /// largest rowid in the left child
+    pub(crate) integer_key: i64,
 }
 
 pub(crate) struct LeafPageHeader {
-    // pub(crate) first_freeblock_offset: Option<u16>,
+    /// offset of the first freeblock in the page, zero when there is none
+    pub(crate) first_freeblock_offset: u16,
     pub(crate) no_cells: u16,
-    // pub(crate) cell_content_offset: u16,
+    /// start of the cell content area, zero stands for 65536
+    pub(crate) cell_content_offset: u16,
     // pub(crate) no_fragmented_bytes: u8,
 }
 
@@ -112,20 +253,52 @@ impl LeafPageHeader {
         payload_size: u64,
     ) -> (usize, Option<usize>) {
         let usable = db_header.usable_page_size();
-        let max_local = usable - 35;
+        local_and_overflow_size(usable, usable - 35, payload_size)
+    }
+}
 
-        if payload_size as usize <= max_local {
-            return (payload_size as usize, None);
-        }
+/// split a payload into the part stored on the b-tree page and the part spilled to overflow pages
+/// returns (local_size, overflow_size) where overflow_size is None if payload fits locally
+#[inline(always)]
+fn local_and_overflow_size(
+    usable: usize,
+    max_local: usize,
+    payload_size: u64,
+) -> (usize, Option<usize>) {
+    if payload_size as usize <= max_local {
+        return (payload_size as usize, None);
+    }
 
-        // payload doesn't fit locally, calculate local and overflow portions
-        let min_local = ((usable - 12) * 32 / 255) - 23;
-        let k = min_local + ((payload_size as usize - min_local) % (usable - 4));
-        let local_size = if k <= max_local { k } else { min_local };
-        let overflow_size = payload_size as usize - local_size;
+    // payload doesn't fit locally, calculate local and overflow portions
+    let min_local = ((usable - 12) * 32 / 255) - 23;
+    let k = min_local + ((payload_size as usize - min_local) % (usable - 4));
+    let local_size = if k <= max_local { k } else { min_local };
+    let overflow_size = payload_size as usize - local_size;
 
-        (local_size, Some(overflow_size))
-    }
+    (local_size, Some(overflow_size))
+}
+
+/// Interior index B-tree page
+pub(crate) struct InteriorIndexPage<'a> {
+    pub(crate) header: InteriorPageHeader,
+    pub(crate) cells: Vec<InteriorIndexCell<'a>>,
+}
+
+pub(crate) struct InteriorIndexCell<'a> {
+    pub(crate) left_child_page_no: u32,
+    pub(crate) cell: IndexCell<'a>,
+}
+
+/// Leaf index B-tree page
+pub(crate) struct LeafIndexPage<'a> {
+    pub(crate) cells: Vec<IndexCell<'a>>,
+}
+
+/// payload of an index b-tree cell, the record is decoded once the overflow chain is joined
+pub struct IndexCell<'a> {
+    pub payload_size: u64,
+    pub overflow_page_no: Option<u32>,
+    pub(crate) local_payload: &'a [u8],
 }
 
 pub(crate) struct LeafTablePage<'a> {
@@ -153,7 +326,8 @@ pub struct LeafTableCell<'a> {
     pub rowid: u64,
     pub payload: TableCellPayload,
     pub overflow_page_no: Option<u32>,
-    pub(crate) column_values: Option<Vec<Option<Payload<'a>>>>,
+    /// the part of the payload stored on the leaf page
+    pub(crate) local_payload: &'a [u8],
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Hash)]
@@ -209,7 +383,7 @@ impl SerialType {
             SerialType::F64 => 8,
             SerialType::Const0 => 0,
             SerialType::Const1 => 0,
-            SerialType::Reserved => unimplemented!("reserved"),
+            SerialType::Reserved => 0,
             SerialType::Blob(n) => ((n - 12) / 2).try_into().unwrap(),
             SerialType::Text(n) => ((n - 13) / 2).try_into().unwrap(),
         }
@@ -229,11 +403,15 @@ impl<'a> RawText<'a> {
         match text_encoding {
             TextEncoding::Utf8 => {
                 let s = if self.0.len() < SIMD_CHUNK_SIZE {
-                    std::str::from_utf8(self.0).expect("invalid UTF-8 in SQLite database")
+                    std::str::from_utf8(self.0).ok()
                 } else {
-                    simd_from_utf8(self.0).expect("invalid UTF-8 in SQLite database")
+                    simd_from_utf8(self.0).ok()
                 };
-                Cow::Borrowed(s)
+                // corrupt text is replaced rather than trusted
+                match s {
+                    Some(s) => Cow::Borrowed(s),
+                    None => String::from_utf8_lossy(self.0),
+                }
             }
             TextEncoding::Utf16Le => {
                 let u16_slice: Vec<_> = self
@@ -241,9 +419,7 @@ impl<'a> RawText<'a> {
                     .chunks_exact(2)
                     .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
                     .collect();
-                let decoded =
-                    String::from_utf16(&u16_slice).expect("invalid UTF-16 LE in SQLite database");
-                Cow::Owned(decoded)
+                Cow::Owned(String::from_utf16_lossy(&u16_slice))
             }
             TextEncoding::Utf16Be => {
                 let u16_slice: Vec<_> = self
@@ -251,9 +427,7 @@ impl<'a> RawText<'a> {
                     .chunks_exact(2)
                     .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
                     .collect();
-                let decoded =
-                    String::from_utf16(&u16_slice).expect("invalid UTF-16 BE in SQLite database");
-                Cow::Owned(decoded)
+                Cow::Owned(String::from_utf16_lossy(&u16_slice))
             }
         }
     }
@@ -274,6 +448,16 @@ pub enum Payload<'a> {
 }
 
 impl<'a> Payload<'a> {
+    /// SQLite storage class of the value
+    pub fn storage_class(&self) -> &'static str {
+        match self {
+            Payload::I64(_) => "INTEGER",
+            Payload::F64(_) => "REAL",
+            Payload::Text(_) => "TEXT",
+            Payload::Blob(_) => "BLOB",
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn as_u32(&self) -> Option<u32> {
         match self {
@@ -283,6 +467,28 @@ impl<'a> Payload<'a> {
     }
 }
 
+/// constant DEFAULT of a column, an owned [`Payload`]
+///
+/// text is kept in the database text encoding, like the text of a record
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum DefaultValue {
+    I64(i64),
+    F64(f64),
+    Blob(Vec<u8>),
+    Text(Vec<u8>),
+}
+
+impl DefaultValue {
+    pub(crate) fn as_payload(&self) -> Payload<'_> {
+        match self {
+            DefaultValue::I64(n) => Payload::I64(*n),
+            DefaultValue::F64(n) => Payload::F64(*n),
+            DefaultValue::Blob(b) => Payload::Blob(b),
+            DefaultValue::Text(t) => Payload::Text(RawText::new(t)),
+        }
+    }
+}
+
 impl<'a> From<&'a str> for Payload<'a> {
     fn from(value: &'a str) -> Self {
         Payload::Text(value.into())
@@ -308,3 +514,51 @@ impl<'a> From<f64> for Payload<'a> {
 }
 
 pub(crate) type OverflowPage<'a> = (Option<u32>, &'a [u8]);
+
+/// next trunk page and the leaf pages listed on a freelist trunk page
+pub(crate) type FreelistTrunkPage = (Option<u32>, Vec<u32>);
+
+/// 32-byte header at the start of a `-wal` file
+#[derive(Debug, Clone)]
+pub struct WalHeader {
+    pub magic: u32,
+    pub format_version: u32,
+    pub page_size: u32,
+    pub checkpoint_seq: u32,
+    pub salt1: u32,
+    pub salt2: u32,
+    pub checksum1: u32,
+    pub checksum2: u32,
+}
+
+impl WalHeader {
+    /// checksums are computed over big-endian words when the low bit of the magic is set
+    #[inline(always)]
+    pub(crate) fn big_endian_checksum(&self) -> bool {
+        self.magic & 1 == 1
+    }
+}
+
+/// 24-byte header in front of every page image in the WAL
+pub(crate) struct WalFrameHeader {
+    pub(crate) pageno: u32,
+    /// size of the database in pages after the commit, zero for non-commit frames
+    pub(crate) db_size: u32,
+    pub(crate) salt1: u32,
+    pub(crate) salt2: u32,
+    pub(crate) checksum1: u32,
+    pub(crate) checksum2: u32,
+}
+
+/// header of a segment of a rollback journal, padded to `sector_size` bytes
+#[derive(Debug, Clone)]
+pub struct JournalHeader {
+    /// page records in the segment, `u32::MAX` when they run to the end of the file
+    pub record_count: u32,
+    /// starting value of the page record checksums
+    pub checksum_nonce: u32,
+    /// size of the database in pages before the transaction started
+    pub db_size: u32,
+    pub sector_size: u32,
+    pub page_size: u32,
+}
@@ -0,0 +1,227 @@
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::error;
+use crate::HashMap;
+
+/// bytes of a page, borrowed from the pager or shared with its cache
+#[derive(Clone, Debug)]
+pub enum PageRef<'a> {
+    Borrowed(&'a [u8]),
+    Shared(Arc<[u8]>),
+}
+
+impl Deref for PageRef<'_> {
+    type Target = [u8];
+
+    #[inline(always)]
+    fn deref(&self) -> &[u8] {
+        match self {
+            PageRef::Borrowed(bytes) => bytes,
+            PageRef::Shared(bytes) => bytes,
+        }
+    }
+}
+
+/// where the pages of a database file come from
+///
+/// anything holding the whole file as `AsRef<[u8]>` is a pager, a memory map or a
+/// `Vec<u8>` read into memory, those hand out their pages without a copy
+pub trait Pager: Sync {
+    /// page `pageno` (1-based) of a file with pages of `page_size` bytes, `None` for page 0
+    /// and when the file ends before the page does
+    fn page(&self, pageno: u32, page_size: usize) -> error::Result<Option<PageRef<'_>>>;
+}
+
+impl<S: AsRef<[u8]> + Sync> Pager for S {
+    #[inline(always)]
+    fn page(&self, pageno: u32, page_size: usize) -> error::Result<Option<PageRef<'_>>> {
+        let Some(index) = (pageno as usize).checked_sub(1) else {
+            return Ok(None);
+        };
+        let start = page_size * index;
+        Ok(self
+            .as_ref()
+            .get(start..start + page_size)
+            .map(PageRef::Borrowed))
+    }
+}
+
+/// reads pages with positioned reads and keeps the most recently used ones in memory
+///
+/// there is no memory map, so it works on filesystems that can't map files and a file
+/// truncated while it is read is an error rather than a SIGBUS
+pub struct PreadPager {
+    file: File,
+    cache: Mutex<PageCache>,
+}
+
+impl PreadPager {
+    /// pages kept by [`PreadPager::open`], 8 MiB of 4 KiB pages
+    pub const DEFAULT_CACHE_PAGES: usize = 2048;
+
+    pub fn open<P: AsRef<Path>>(path: P) -> error::Result<PreadPager> {
+        Ok(PreadPager::new(
+            File::open(path)?,
+            Self::DEFAULT_CACHE_PAGES,
+        ))
+    }
+
+    /// read from `file`, caching at most `cache_pages` pages
+    pub fn new(file: File, cache_pages: usize) -> PreadPager {
+        PreadPager {
+            file,
+            cache: Mutex::new(PageCache::new(cache_pages)),
+        }
+    }
+}
+
+impl Pager for PreadPager {
+    fn page(&self, pageno: u32, page_size: usize) -> error::Result<Option<PageRef<'_>>> {
+        let Some(index) = (pageno as u64).checked_sub(1) else {
+            return Ok(None);
+        };
+        let cached = self
+            .cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(pageno)
+            .filter(|page| page.len() == page_size);
+        if let Some(page) = cached {
+            return Ok(Some(PageRef::Shared(page)));
+        }
+
+        let mut buf = vec![0; page_size];
+        match read_exact_at(&self.file, &mut buf, page_size as u64 * index) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        let page: Arc<[u8]> = buf.into();
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(pageno, Arc::clone(&page));
+        Ok(Some(PageRef::Shared(page)))
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+const NIL: usize = usize::MAX;
+
+struct CacheEntry {
+    pageno: u32,
+    page: Arc<[u8]>,
+    /// neighbour used more recently
+    prev: usize,
+    /// neighbour used less recently
+    next: usize,
+}
+
+/// the `capacity` least recently used pages, as a list threaded through `entries`
+struct PageCache {
+    capacity: usize,
+    index: HashMap<u32, usize>,
+    entries: Vec<CacheEntry>,
+    /// most recently used entry
+    head: usize,
+    /// least recently used entry, the next to go
+    tail: usize,
+}
+
+impl PageCache {
+    fn new(capacity: usize) -> Self {
+        PageCache {
+            capacity,
+            index: HashMap::default(),
+            entries: Vec::new(),
+            head: NIL,
+            tail: NIL,
+        }
+    }
+
+    fn get(&mut self, pageno: u32) -> Option<Arc<[u8]>> {
+        let idx = *self.index.get(&pageno)?;
+        self.unlink(idx);
+        self.push_front(idx);
+        Some(Arc::clone(&self.entries[idx].page))
+    }
+
+    fn insert(&mut self, pageno: u32, page: Arc<[u8]>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let idx = if let Some(&idx) = self.index.get(&pageno) {
+            self.unlink(idx);
+            self.entries[idx].page = page;
+            idx
+        } else if self.entries.len() < self.capacity {
+            self.entries.push(CacheEntry {
+                pageno,
+                page,
+                prev: NIL,
+                next: NIL,
+            });
+            self.entries.len() - 1
+        } else {
+            let idx = self.tail;
+            self.unlink(idx);
+            self.index.remove(&self.entries[idx].pageno);
+            self.entries[idx].pageno = pageno;
+            self.entries[idx].page = page;
+            idx
+        };
+
+        self.index.insert(pageno, idx);
+        self.push_front(idx);
+    }
+
+    fn unlink(&mut self, idx: usize) {
+        let (prev, next) = (self.entries[idx].prev, self.entries[idx].next);
+        match prev {
+            NIL => self.head = next,
+            prev => self.entries[prev].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.entries[next].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, idx: usize) {
+        self.entries[idx].prev = NIL;
+        self.entries[idx].next = self.head;
+        match self.head {
+            NIL => self.tail = idx,
+            head => self.entries[head].prev = idx,
+        }
+        self.head = idx;
+    }
+}
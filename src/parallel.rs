@@ -0,0 +1,259 @@
+use std::ops::Bound;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Mutex;
+
+use crate::catalog::ObjectKind;
+use crate::error::{self, SQLiteError};
+use crate::model::{self, Page};
+use crate::{Pager, Reader, TreeWalk, MAX_TREE_DEPTH};
+
+/// rows decoded by a worker are sent to the consumer in chunks of this many
+const CHUNK_SIZE: usize = 1024;
+
+/// every worker takes this many partitions on average, so a slow subtree does not stall the scan
+const PARTITIONS_PER_THREAD: usize = 4;
+
+type RowidRange = (Bound<i64>, Bound<i64>);
+
+/// values decoded from one partition
+type Chunk<T> = error::Result<(usize, Vec<T>)>;
+
+/// subtree of a table b-tree holding the rowids in `(after, up_to]`
+struct Subtree {
+    pageno: u32,
+    after: Option<i64>,
+    up_to: Option<i64>,
+}
+
+/// a partition waiting for a worker, the sender goes away once it is done
+struct Job<T> {
+    partition: usize,
+    rowids: RowidRange,
+    sender: SyncSender<Chunk<T>>,
+}
+
+impl<P: Pager> Reader<P> {
+    /// split `table_name` into at most `partitions` disjoint rowid ranges, in ascending order
+    ///
+    /// the ranges come from the keys of the interior pages, the tree is expanded level by
+    /// level until there are enough subtrees and those are grouped into ranges of similar size
+    pub fn table_partitions(
+        &self,
+        table_name: &str,
+        partitions: usize,
+    ) -> error::Result<Vec<RowidRange>> {
+        let root_pageno = self
+            .find_root_page(ObjectKind::Table, table_name)?
+            .ok_or_else(|| SQLiteError::TableNotFound(table_name.to_owned()))?;
+
+        let mut walk = TreeWalk::default();
+        walk.visit(root_pageno)?;
+        let mut subtrees = vec![Subtree {
+            pageno: root_pageno,
+            after: None,
+            up_to: None,
+        }];
+
+        for _ in 0..MAX_TREE_DEPTH {
+            if subtrees.len() >= partitions {
+                break;
+            }
+
+            let mut children = Vec::with_capacity(subtrees.len());
+            let mut expanded = false;
+            for subtree in subtrees {
+                let page_bytes = self.page_bytes(subtree.pageno)?;
+                let Page::InteriorTable(interior) = self.parse_page(subtree.pageno, &page_bytes)?
+                else {
+                    children.push(subtree);
+                    continue;
+                };
+
+                let mut after = subtree.after;
+                for cell in &interior.cells {
+                    walk.visit(cell.left_child_page_no)?;
+                    children.push(Subtree {
+                        pageno: cell.left_child_page_no,
+                        after,
+                        up_to: Some(cell.integer_key),
+                    });
+                    after = Some(cell.integer_key);
+                }
+                walk.visit(interior.header.rightmost_pointer)?;
+                children.push(Subtree {
+                    pageno: interior.header.rightmost_pointer,
+                    after,
+                    up_to: subtree.up_to,
+                });
+                expanded = true;
+            }
+
+            subtrees = children;
+            if !expanded {
+                break;
+            }
+        }
+
+        let partitions = partitions.clamp(1, subtrees.len().max(1));
+        let ranges = (0..partitions)
+            .map(|partition| {
+                let first = &subtrees[partition * subtrees.len() / partitions];
+                let last = &subtrees[(partition + 1) * subtrees.len() / partitions - 1];
+                let start = match (partition, first.after) {
+                    (0, _) | (_, None) => Bound::Unbounded,
+                    (_, Some(after)) => Bound::Excluded(after),
+                };
+                let end = match (partition + 1 == partitions, last.up_to) {
+                    (true, _) | (_, None) => Bound::Unbounded,
+                    (false, Some(up_to)) => Bound::Included(up_to),
+                };
+                (start, end)
+            })
+            .collect();
+
+        Ok(ranges)
+    }
+
+    /// scan `table_name` on `threads` workers
+    ///
+    /// the table is split with [`Reader::table_partitions`], every worker decodes whole
+    /// partitions and turns each row into a `T` with `map`. `consume` runs on the calling
+    /// thread and gets the partition index with every value. rows of one partition always
+    /// come in rowid order, with `ordered` the partitions are handed over one after another
+    /// so the whole table comes back in rowid order
+    ///
+    /// without `ordered` the values of the partitions being scanned are interleaved, in
+    /// whatever order the workers produce them, a consumer that needs each partition on its
+    /// own has to keep them apart by the partition index
+    pub fn stream_table_rows_parallel<T, M, C>(
+        &self,
+        table_name: &str,
+        threads: usize,
+        ordered: bool,
+        map: M,
+        mut consume: C,
+    ) -> error::Result<()>
+    where
+        T: Send,
+        M: Fn(&model::LeafTableCell<'_>, &Vec<Option<model::Payload<'_>>>) -> error::Result<T>
+            + Sync,
+        C: FnMut(usize, T) -> error::Result<()>,
+    {
+        let threads = threads.max(1);
+        let partitions = self.table_partitions(table_name, threads * PARTITIONS_PER_THREAD)?;
+
+        let mut jobs = Vec::with_capacity(partitions.len());
+        let mut receivers = Vec::new();
+        let (shared_sender, shared_receiver) = sync_channel(threads * PARTITIONS_PER_THREAD);
+        for (partition, rowids) in partitions.into_iter().enumerate() {
+            let sender = if ordered {
+                let (sender, receiver) = sync_channel(PARTITIONS_PER_THREAD);
+                receivers.push(receiver);
+                sender
+            } else {
+                shared_sender.clone()
+            };
+            jobs.push(Job {
+                partition,
+                rowids,
+                sender,
+            });
+        }
+        drop(shared_sender);
+        if !ordered {
+            receivers.push(shared_receiver);
+        }
+
+        // jobs are taken in partition order, so the partition the consumer waits for is
+        // always being worked on and a worker blocked on a full channel can't stall it
+        let jobs = Mutex::new(jobs.into_iter());
+        let cancelled = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    while let Some(job) = next_job(&jobs, &cancelled) {
+                        self.scan_partition(table_name, job, &map, &cancelled);
+                    }
+                });
+            }
+
+            let result = consume_chunks(receivers, &mut consume);
+            if result.is_err() {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+            result
+        })
+    }
+
+    /// decode the rows of one partition and send them to the consumer in chunks
+    fn scan_partition<T, M>(&self, table_name: &str, job: Job<T>, map: &M, cancelled: &AtomicBool)
+    where
+        M: Fn(&model::LeafTableCell<'_>, &Vec<Option<model::Payload<'_>>>) -> error::Result<T>,
+    {
+        let Job {
+            partition,
+            rowids,
+            sender,
+        } = job;
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+
+        let result = self.stream_table_rows_range(table_name, rowids, |cell, column_values| {
+            chunk.push(map(cell, column_values)?);
+            if chunk.len() >= CHUNK_SIZE {
+                let full = std::mem::replace(&mut chunk, Vec::with_capacity(CHUNK_SIZE));
+                send_chunk(&sender, partition, full, cancelled)?;
+            }
+            Ok(())
+        });
+
+        // the consumer is gone once a send fails, there is nobody left to report to
+        match result {
+            Ok(()) if chunk.is_empty() => {}
+            Ok(()) => {
+                let _ = send_chunk(&sender, partition, chunk, cancelled);
+            }
+            Err(err) => {
+                let _ = sender.send(Err(err));
+            }
+        }
+    }
+}
+
+#[inline]
+fn next_job<T>(jobs: &Mutex<std::vec::IntoIter<Job<T>>>, cancelled: &AtomicBool) -> Option<Job<T>> {
+    if cancelled.load(Ordering::Relaxed) {
+        return None;
+    }
+    jobs.lock().ok()?.next()
+}
+
+#[inline]
+fn send_chunk<T>(
+    sender: &SyncSender<Chunk<T>>,
+    partition: usize,
+    chunk: Vec<T>,
+    cancelled: &AtomicBool,
+) -> error::Result<()> {
+    if cancelled.load(Ordering::Relaxed) || sender.send(Ok((partition, chunk))).is_err() {
+        return Err(SQLiteError::Other("parallel scan cancelled".into()));
+    }
+    Ok(())
+}
+
+/// hand every value to `consume`, one receiver after another
+fn consume_chunks<T, C>(receivers: Vec<Receiver<Chunk<T>>>, consume: &mut C) -> error::Result<()>
+where
+    C: FnMut(usize, T) -> error::Result<()>,
+{
+    for receiver in receivers {
+        for chunk in receiver {
+            let (partition, values) = chunk?;
+            for value in values {
+                consume(partition, value)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,12 @@
+use crate::catalog::ObjectKind;
 use crate::error::SQLiteError;
-use crate::model::{LeafTableCell, Payload, SerialType, TextEncoding};
-use crate::Reader;
-use arrow::array::{ArrayRef, BinaryBuilder, Float64Builder, Int64Builder, StringBuilder};
-use arrow::datatypes::{DataType, Schema};
+use crate::model::{Affinity, Payload, TextEncoding};
+use crate::{Pager, Reader};
+use arrow::array::builder::NullBufferBuilder;
+use arrow::array::{
+    ArrayRef, BinaryBuilder, Float64Builder, Int64Builder, StringBuilder, StructArray,
+};
+use arrow::datatypes::{DataType, Fields, Schema};
 use arrow::record_batch::RecordBatch;
 use arrow_schema::Field;
 use parquet::arrow::ArrowWriter;
@@ -14,42 +18,152 @@ use std::path::Path;
 use std::sync::mpsc::{Receiver, SyncSender};
 use std::sync::Arc;
 
-impl SerialType {
+/// rows looked at to find the type of a column whose declared type does not fix one
+const SAMPLE_ROWS: usize = 1000;
+
+/// what to do with a value whose storage class does not match the type of its column
+///
+/// SQLite lets any column hold any value, the arrow type of a column comes from its
+/// declared type (or a sample of its values) and some values may not fit it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MixedTypes {
+    /// convert between integers and reals, write NULL for anything else, the values that
+    /// lost something are counted in [`Exported::coerced`]
+    #[default]
+    Coerce,
+    /// write columns holding values that don't fit as a struct with one child per storage
+    /// class, the table is read one more time to find them
+    Lossless,
+    /// fail on the first value that doesn't fit
+    Strict,
+}
+
+impl std::str::FromStr for MixedTypes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "coerce" => Ok(MixedTypes::Coerce),
+            "lossless" => Ok(MixedTypes::Lossless),
+            "strict" => Ok(MixedTypes::Strict),
+            _ => Err(format!(
+                "unknown mode `{s}`, expected coerce, lossless or strict"
+            )),
+        }
+    }
+}
+
+impl Affinity {
+    /// arrow type of a column with this affinity, NUMERIC columns hold integers and reals
+    /// side by side and are written as reals
     #[inline]
-    fn to_arrow(&self) -> (DataType, bool) {
+    fn to_arrow(self) -> DataType {
         match self {
-            SerialType::Null => (DataType::Binary, true),
-            SerialType::I8
-            | SerialType::I16
-            | SerialType::I24
-            | SerialType::I32
-            | SerialType::I48
-            | SerialType::I64
-            | SerialType::Const0
-            | SerialType::Const1 => (DataType::Int64, true),
-            SerialType::F64 => (DataType::Float64, true),
-            SerialType::Text(_) => (DataType::Utf8, true),
-            SerialType::Blob(_) => (DataType::Binary, true),
-            SerialType::Reserved => (DataType::Binary, true),
+            Affinity::Integer => DataType::Int64,
+            Affinity::Real | Affinity::Numeric => DataType::Float64,
+            Affinity::Text => DataType::Utf8,
+            Affinity::Blob => DataType::Binary,
         }
     }
 }
 
+/// arrow type of a column whose values don't share one storage class
+///
+/// Parquet has no union type, so exactly one child of the struct is set for every
+/// non-null value
+pub fn mixed_data_type() -> DataType {
+    DataType::Struct(Fields::from(vec![
+        Field::new("integer", DataType::Int64, true),
+        Field::new("real", DataType::Float64, true),
+        Field::new("text", DataType::Utf8, true),
+        Field::new("blob", DataType::Binary, true),
+    ]))
+}
+
 enum ColumnBuilder {
     Int64(Int64Builder),
     Float64(Float64Builder),
     Utf8(StringBuilder),
     Binary(BinaryBuilder),
+    Mixed(Box<MixedBuilder>),
+}
+
+/// builder for a column of [`mixed_data_type`]
+struct MixedBuilder {
+    fields: Fields,
+    integer: Int64Builder,
+    real: Float64Builder,
+    text: StringBuilder,
+    blob: BinaryBuilder,
+    nulls: NullBufferBuilder,
+}
+
+impl MixedBuilder {
+    fn new(fields: &Fields, capacity: usize) -> Self {
+        MixedBuilder {
+            fields: fields.clone(),
+            integer: Int64Builder::with_capacity(capacity),
+            real: Float64Builder::with_capacity(capacity),
+            text: StringBuilder::with_capacity(capacity, capacity * 32),
+            blob: BinaryBuilder::with_capacity(capacity, capacity * 64),
+            nulls: NullBufferBuilder::new(capacity),
+        }
+    }
+
+    fn append(&mut self, value: Option<&Payload>, text_encoding: TextEncoding) {
+        match value {
+            Some(Payload::I64(v)) => self.integer.append_value(*v),
+            _ => self.integer.append_null(),
+        }
+        match value {
+            Some(Payload::F64(v)) => self.real.append_value(*v),
+            _ => self.real.append_null(),
+        }
+        match value {
+            Some(Payload::Text(t)) => self.text.append_value(t.decode(text_encoding)),
+            _ => self.text.append_null(),
+        }
+        match value {
+            Some(Payload::Blob(b)) => self.blob.append_value(b),
+            _ => self.blob.append_null(),
+        }
+        self.nulls.append(value.is_some());
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        let children: Vec<ArrayRef> = vec![
+            Arc::new(self.integer.finish()),
+            Arc::new(self.real.finish()),
+            Arc::new(self.text.finish()),
+            Arc::new(self.blob.finish()),
+        ];
+        Arc::new(StructArray::new(
+            self.fields.clone(),
+            children,
+            self.nulls.finish(),
+        ))
+    }
+}
+
+/// what [`export_table`] wrote
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Exported {
+    pub rows: usize,
+    /// values [`MixedTypes::Coerce`] truncated, rounded or replaced by NULL
+    pub coerced: usize,
 }
 
 pub struct ParquetContext {
     schema: Arc<Schema>,
     sender: SyncSender<RecordBatch>,
     writer_handle: std::thread::JoinHandle<Result<(), SQLiteError>>,
-    rowid_builder: Int64Builder,
+    /// `None` for WITHOUT ROWID tables
+    rowid_builder: Option<Int64Builder>,
     column_builders: Vec<ColumnBuilder>,
     columns: Vec<ArrayRef>,
     batch_size: usize,
+    mixed_types: MixedTypes,
+    coerced: usize,
 }
 
 impl ColumnBuilder {
@@ -63,6 +177,9 @@ impl ColumnBuilder {
             DataType::Binary => {
                 ColumnBuilder::Binary(BinaryBuilder::with_capacity(capacity, capacity * 64))
             }
+            DataType::Struct(fields) => {
+                ColumnBuilder::Mixed(Box::new(MixedBuilder::new(fields, capacity)))
+            }
             _ => ColumnBuilder::Binary(BinaryBuilder::with_capacity(capacity, capacity * 64)),
         }
     }
@@ -73,6 +190,34 @@ impl ColumnBuilder {
             ColumnBuilder::Float64(b) => b.append_null(),
             ColumnBuilder::Utf8(b) => b.append_null(),
             ColumnBuilder::Binary(b) => b.append_null(),
+            ColumnBuilder::Mixed(b) => b.append(None, TextEncoding::Utf8),
+        }
+    }
+
+    /// whether `value` can be written to the column without losing anything
+    #[inline]
+    fn fits(&self, value: &Payload) -> bool {
+        match (self, value) {
+            (ColumnBuilder::Int64(_), Payload::I64(_)) => true,
+            (ColumnBuilder::Int64(_), Payload::F64(v)) => {
+                v.fract() == 0.0 && *v >= i64::MIN as f64 && *v < i64::MAX as f64
+            }
+            (ColumnBuilder::Float64(_), Payload::F64(_)) => true,
+            (ColumnBuilder::Float64(_), Payload::I64(v)) => (*v as f64) as i128 == *v as i128,
+            (ColumnBuilder::Utf8(_), Payload::Text(_)) => true,
+            (ColumnBuilder::Binary(_), Payload::Blob(_)) => true,
+            (ColumnBuilder::Mixed(_), _) => true,
+            _ => false,
+        }
+    }
+
+    fn data_type_name(&self) -> &'static str {
+        match self {
+            ColumnBuilder::Int64(_) => "Int64",
+            ColumnBuilder::Float64(_) => "Float64",
+            ColumnBuilder::Utf8(_) => "Utf8",
+            ColumnBuilder::Binary(_) => "Binary",
+            ColumnBuilder::Mixed(_) => "mixed",
         }
     }
 
@@ -98,29 +243,24 @@ impl ColumnBuilder {
                 *b = BinaryBuilder::with_capacity(capacity, capacity * 64);
                 array
             }
+            ColumnBuilder::Mixed(b) => b.finish(),
         }
     }
 }
 
+/// `with_rowid` puts a `rowid` column in front, for tables whose rowid has no alias
 pub(crate) fn build_arrow_schema(
-    column_types: &[SerialType],
+    column_types: &[DataType],
+    with_rowid: bool,
     column_names: Option<&[String]>,
 ) -> Arc<Schema> {
     let mut fields = Vec::new();
 
-    fields.push(Field::new("rowid", DataType::Int64, false));
-
-    let skip_first = column_types
-        .first()
-        .is_some_and(|t| matches!(t, SerialType::Null));
-    let columns_to_process = if skip_first {
-        &column_types[1..]
-    } else {
-        column_types
-    };
+    if with_rowid {
+        fields.push(Field::new("rowid", DataType::Int64, false));
+    }
 
-    for (idx, serial_type) in columns_to_process.iter().enumerate() {
-        let (data_type, nullable) = serial_type.to_arrow();
+    for (idx, data_type) in column_types.iter().enumerate() {
         let column_name = if let Some(names) = column_names {
             names
                 .get(idx)
@@ -130,23 +270,153 @@ pub(crate) fn build_arrow_schema(
             format!("col_{}", idx)
         };
 
-        fields.push(Field::new(column_name, data_type, nullable));
+        fields.push(Field::new(column_name, data_type.clone(), true));
     }
 
     Arc::new(Schema::new(fields))
 }
 
+#[inline]
+fn payload_to_arrow(value: &Payload) -> DataType {
+    match value {
+        Payload::I64(_) => DataType::Int64,
+        Payload::F64(_) => DataType::Float64,
+        Payload::Text(_) => DataType::Utf8,
+        Payload::Blob(_) => DataType::Binary,
+    }
+}
+
+/// arrow type a declared column type fixes, `None` for a column without one
+#[inline]
+fn declared_data_type(declared_type: Option<&str>) -> Option<DataType> {
+    declared_type.map(|declared| Affinity::from_declared_type(Some(declared)).to_arrow())
+}
+
+/// arrow type of every record column
+///
+/// a declared type decides through its affinity, columns without one take the type of the
+/// first non-null value in `sampled`, Binary if there is none
+fn column_data_types(
+    declared_types: &[Option<&str>],
+    sampled: &[Option<DataType>],
+) -> Vec<DataType> {
+    (0..declared_types.len().max(sampled.len()))
+        .map(|idx| {
+            declared_data_type(declared_types.get(idx).copied().flatten())
+                .or_else(|| sampled.get(idx).cloned().flatten())
+                .unwrap_or(DataType::Binary)
+        })
+        .collect()
+}
+
+/// remember the type of the first non-null value of every column
+#[inline]
+fn sample_values(values: &[Option<Payload>], sampled: &mut Vec<Option<DataType>>) {
+    if sampled.len() < values.len() {
+        sampled.resize(values.len(), None);
+    }
+    for (value, sampled) in values.iter().zip(sampled.iter_mut()) {
+        if let (Some(value), None) = (value, &sampled) {
+            *sampled = Some(payload_to_arrow(value));
+        }
+    }
+}
+
+/// the types of a table, and the columns holding values that don't fit them
+///
+/// NUMERIC columns holding text or blobs are always mixed, any other column only when
+/// `lossless`
+struct TypeScan<'a> {
+    declared_types: Vec<Option<&'a str>>,
+    numeric: Vec<bool>,
+    lossless: bool,
+    sampled: Vec<Option<DataType>>,
+    mixed: Vec<bool>,
+}
 
-    
+impl<'a> TypeScan<'a> {
+    fn new(declared_types: Vec<Option<&'a str>>, lossless: bool) -> Self {
+        let numeric = declared_types
+            .iter()
+            .map(|declared| {
+                declared.is_some() && Affinity::from_declared_type(*declared) == Affinity::Numeric
+            })
+            .collect();
+        TypeScan {
+            declared_types,
+            numeric,
+            lossless,
+            sampled: Vec::new(),
+            mixed: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, values: &[Option<Payload>]) {
+        sample_values(values, &mut self.sampled);
+        if self.mixed.len() < values.len() {
+            self.mixed.resize(values.len(), false);
+        }
+
+        for (idx, value) in values.iter().enumerate() {
+            let Some(value) = value else { continue };
+            if self.numeric.get(idx) == Some(&true)
+                && matches!(value, Payload::Text(_) | Payload::Blob(_))
+            {
+                self.mixed[idx] = true;
+            }
+            if !self.lossless || self.mixed[idx] {
+                continue;
+            }
+            let data_type = declared_data_type(self.declared_types.get(idx).copied().flatten())
+                .or_else(|| self.sampled[idx].clone())
+                .unwrap_or(DataType::Binary);
+            if !ColumnBuilder::new(&data_type, 0).fits(value) {
+                self.mixed[idx] = true;
+            }
+        }
+    }
+
+    /// like [`column_data_types`], columns that hold values of another type are mixed
+    fn column_types(&self) -> Vec<DataType> {
+        let mut column_types = column_data_types(&self.declared_types, &self.sampled);
+        for (data_type, _) in column_types
+            .iter_mut()
+            .zip(&self.mixed)
+            .filter(|(_, &mixed)| mixed)
+        {
+            *data_type = mixed_data_type();
+        }
+        column_types
+    }
+}
+
+/// `with_rowid` adds a `rowid` column in front of `column_types`
 pub fn context_init<P: AsRef<Path>>(
-    cell: &LeafTableCell,
+    with_rowid: bool,
     output_path: P,
     batch_size: usize,
     column_names: Option<&[String]>,
+    column_types: &[DataType],
+    mixed_types: MixedTypes,
 ) -> Result<ParquetContext, SQLiteError> {
-    let column_types = cell.payload.column_types.clone();
-    let arrow_schema = build_arrow_schema(&column_types, column_names);
+    let arrow_schema = build_arrow_schema(column_types, with_rowid, column_names);
+
+    context_new(
+        arrow_schema,
+        output_path,
+        batch_size,
+        with_rowid,
+        mixed_types,
+    )
+}
 
+fn context_new<P: AsRef<Path>>(
+    arrow_schema: Arc<Schema>,
+    output_path: P,
+    batch_size: usize,
+    with_rowid: bool,
+    mixed_types: MixedTypes,
+) -> Result<ParquetContext, SQLiteError> {
     let (tx, rx) = std::sync::mpsc::sync_channel::<RecordBatch>(2);
 
     let output_path = output_path.as_ref().to_path_buf();
@@ -155,11 +425,11 @@ pub fn context_init<P: AsRef<Path>>(
         write_batches(rx, &output_path, schema_clone)
     });
 
-    let rowid_builder = Int64Builder::with_capacity(batch_size);
+    let rowid_builder = with_rowid.then(|| Int64Builder::with_capacity(batch_size));
     let column_builders = arrow_schema
         .fields()
         .iter()
-        .skip(1)
+        .skip(with_rowid as usize)
         .map(|f| ColumnBuilder::new(f.data_type(), batch_size))
         .collect();
     let columns = Vec::with_capacity(arrow_schema.fields().len());
@@ -172,32 +442,43 @@ pub fn context_init<P: AsRef<Path>>(
         column_builders,
         columns,
         batch_size,
+        mixed_types,
+        coerced: 0,
     })
 }
 
 fn process_row(
+    rowid: i64,
     column_values: &[Option<Payload>],
-    column_builders: &mut [ColumnBuilder],
+    context: &mut ParquetContext,
     text_encoding: TextEncoding,
-    full_payload: Option<&Vec<u8>>,
-) {
-    let values_to_write = if column_values.first().is_some_and(|v| v.is_none()) {
-        &column_values[1..]
-    } else {
-        column_values
-    };
-
-    for (value, column_builder) in values_to_write.iter().zip(column_builders.iter_mut()) {
+) -> Result<(), SQLiteError> {
+    append_values(Some(rowid), column_values, context, text_encoding)
+}
+
+fn append_values(
+    rowid: Option<i64>,
+    column_values: &[Option<Payload>],
+    context: &mut ParquetContext,
+    text_encoding: TextEncoding,
+) -> Result<(), SQLiteError> {
+    if context.mixed_types == MixedTypes::Strict {
+        check_values(rowid, column_values, context)?;
+    }
+
+    if let (Some(rowid_builder), Some(rowid)) = (context.rowid_builder.as_mut(), rowid) {
+        rowid_builder.append_value(rowid);
+    }
+
+    let column_builders = context.column_builders.as_mut_slice();
+    for (value, column_builder) in column_values.iter().zip(column_builders.iter_mut()) {
         let Some(payload) = value else {
-            if let ColumnBuilder::Binary(builder) = column_builder {
-                if let Some(data) = full_payload {
-                    builder.append_value(data);
-                    continue;
-                }
-            }
             column_builder.append_null();
             continue;
         };
+        if !column_builder.fits(payload) {
+            context.coerced += 1;
+        }
 
         match column_builder {
             ColumnBuilder::Int64(builder) => match payload {
@@ -221,22 +502,57 @@ fn process_row(
                 Payload::Blob(b) => builder.append_value(b),
                 _ => builder.append_null(),
             },
+            ColumnBuilder::Mixed(builder) => builder.append(Some(payload), text_encoding),
         }
     }
 
-    for column_builder in column_builders.iter_mut().skip(values_to_write.len()) {
+    for column_builder in column_builders.iter_mut().skip(column_values.len()) {
         column_builder.append_null();
     }
+
+    Ok(())
+}
+
+/// fail on the first value that doesn't fit its column
+#[cold]
+fn check_values(
+    rowid: Option<i64>,
+    column_values: &[Option<Payload>],
+    context: &ParquetContext,
+) -> Result<(), SQLiteError> {
+    let first_column = context.rowid_builder.is_some() as usize;
+    let mismatch = column_values
+        .iter()
+        .zip(&context.column_builders)
+        .enumerate()
+        .find_map(|(idx, (value, builder))| {
+            value
+                .as_ref()
+                .filter(|value| !builder.fits(value))
+                .map(|value| (idx, value, builder))
+        });
+
+    match mismatch {
+        None => Ok(()),
+        Some((idx, value, builder)) => Err(SQLiteError::TypeMismatch {
+            rowid,
+            column: context.schema.field(first_column + idx).name().clone(),
+            found: value.storage_class(),
+            expected: builder.data_type_name(),
+        }),
+    }
 }
 
 fn flush_rows(context: &mut ParquetContext, last: bool) -> Result<(), SQLiteError> {
     context.columns.clear();
 
-    let rowid_array = Arc::new(context.rowid_builder.finish());
-    if !last {
-        context.rowid_builder = Int64Builder::with_capacity(context.batch_size);
+    if let Some(rowid_builder) = context.rowid_builder.as_mut() {
+        let rowid_array = Arc::new(rowid_builder.finish());
+        if !last {
+            *rowid_builder = Int64Builder::with_capacity(context.batch_size);
+        }
+        context.columns.push(rowid_array as ArrayRef);
     }
-    context.columns.push(rowid_array as ArrayRef);
 
     for builder in context.column_builders.iter_mut() {
         context
@@ -256,62 +572,172 @@ fn flush_rows(context: &mut ParquetContext, last: bool) -> Result<(), SQLiteErro
 }
 
 pub fn export_table<P: AsRef<Path>>(
-    reader: &Reader<impl AsRef<[u8]> + Sync>,
+    reader: &Reader<impl Pager>,
     table_name: &str,
     output_path: P,
     batch_size: usize,
-) -> Result<usize, SQLiteError> {
+    mixed_types: MixedTypes,
+) -> Result<Exported, SQLiteError> {
     let text_encoding = reader.header.db_text_encoding;
     let mut total_rows = 0;
 
-    let column_names = reader
-        .get_tables_map()?
-        .get(table_name)
+    let table_schema = reader
+        .find_object(ObjectKind::Table, table_name)?
         .ok_or_else(|| SQLiteError::TableNotFound(table_name.to_owned()))?
-        .as_ref()
-        .map(|schema| schema.get_column_names());
+        .table_schema();
+
+    if table_schema.is_some_and(|schema| schema.without_rowid) {
+        return export_without_rowid_table(
+            reader,
+            table_name,
+            output_path,
+            batch_size,
+            mixed_types,
+        );
+    }
 
-    let mut context: Option<ParquetContext> = None;
-    let mut rows_buffered = 0;
+    let column_names = table_schema.map(|schema| schema.get_column_names());
+    let declared_types = table_schema.map_or_else(Vec::new, |schema| schema.declared_types());
 
-    reader.stream_table_rows_sequential(table_name, |cell, column_values| {
-        if context.is_none() {
-            context = Some(context_init(
-                cell,
-                &output_path,
-                batch_size,
-                column_names.as_deref(),
-            )?);
+    // a rowid alias is exported under its own name, other rowids get a column of their own
+    let with_rowid = table_schema.is_none_or(|schema| schema.rowid_alias.is_none());
+
+    let column_types = scan_column_types(declared_types, mixed_types, |add| {
+        if mixed_types == MixedTypes::Lossless {
+            return reader.stream_table_rows_sequential(table_name, |_, column_values| {
+                add(column_values);
+                Ok(())
+            });
+        }
+        for row in reader.table_rows(table_name)? {
+            if !add(&row?.values()?) {
+                break;
+            }
         }
-        let context = context.as_mut().unwrap();
-        context.rowid_builder.append_value(cell.rowid as i64);
+        Ok(())
+    })?;
 
-        let full_payload = if cell.overflow_page_no.is_some() {
-            reader.reconstruct_full_payload(cell).ok()
-        } else {
-            None
-        };
+    let mut context = context_init(
+        with_rowid,
+        &output_path,
+        batch_size,
+        column_names.as_deref(),
+        &column_types,
+        mixed_types,
+    )?;
+    let mut rows_buffered = 0;
 
+    reader.stream_table_rows_sequential(table_name, |cell, column_values| {
         process_row(
+            cell.rowid as i64,
             column_values,
-            context.column_builders.as_mut_slice(),
+            &mut context,
             text_encoding,
-            full_payload.as_ref(),
-        );
+        )?;
 
         rows_buffered += 1;
         total_rows += 1;
 
         if rows_buffered >= batch_size {
-            flush_rows(context, false)?;
+            flush_rows(&mut context, false)?;
             rows_buffered = 0;
         }
 
         Ok(())
     })?;
 
-    let mut context = context.unwrap();
+    finish_export(context, rows_buffered, total_rows)
+}
+
+fn export_without_rowid_table<P: AsRef<Path>>(
+    reader: &Reader<impl Pager>,
+    table_name: &str,
+    output_path: P,
+    batch_size: usize,
+    mixed_types: MixedTypes,
+) -> Result<Exported, SQLiteError> {
+    let text_encoding = reader.header.db_text_encoding;
+    let mut total_rows = 0;
+
+    let table_schema = reader.table_schema(table_name)?;
+    let column_names = table_schema.map(|schema| schema.get_column_names());
+    let declared_types = table_schema.map_or_else(Vec::new, |schema| schema.declared_types());
+
+    // there is no cursor over WITHOUT ROWID tables, the scan is cut short with an error
+    // once the sample is full, any other error comes before that
+    let column_types = scan_column_types(declared_types, mixed_types, |add| {
+        let mut more = true;
+        let scanned = reader.stream_without_rowid_rows(table_name, |column_values| {
+            more = add(column_values);
+            if more {
+                Ok(())
+            } else {
+                Err(SQLiteError::Other(String::new()))
+            }
+        });
+        match scanned {
+            Err(_) if !more => Ok(()),
+            scanned => scanned,
+        }
+    })?;
+
+    let mut context = context_init(
+        false,
+        &output_path,
+        batch_size,
+        column_names.as_deref(),
+        &column_types,
+        mixed_types,
+    )?;
+    let mut rows_buffered = 0;
+
+    reader.stream_without_rowid_rows(table_name, |column_values| {
+        append_values(None, column_values, &mut context, text_encoding)?;
 
+        rows_buffered += 1;
+        total_rows += 1;
+
+        if rows_buffered >= batch_size {
+            flush_rows(&mut context, false)?;
+            rows_buffered = 0;
+        }
+
+        Ok(())
+    })?;
+
+    finish_export(context, rows_buffered, total_rows)
+}
+
+/// arrow type of every column of a table, `scan_rows` hands its rows to `add` until it
+/// returns false
+///
+/// only the first [`SAMPLE_ROWS`] rows are sampled, except with [`MixedTypes::Lossless`]
+/// which has to see every value to find the mixed columns
+fn scan_column_types<S>(
+    declared_types: Vec<Option<&str>>,
+    mixed_types: MixedTypes,
+    scan_rows: S,
+) -> Result<Vec<DataType>, SQLiteError>
+where
+    S: FnOnce(&mut dyn FnMut(&[Option<Payload>]) -> bool) -> Result<(), SQLiteError>,
+{
+    let lossless = mixed_types == MixedTypes::Lossless;
+    let mut scan = TypeScan::new(declared_types, lossless);
+    let mut rows = 0;
+    scan_rows(&mut |column_values| {
+        scan.add(column_values);
+        rows += 1;
+        lossless || rows < SAMPLE_ROWS
+    })?;
+
+    Ok(scan.column_types())
+}
+
+fn finish_export(
+    mut context: ParquetContext,
+    rows_buffered: usize,
+    rows: usize,
+) -> Result<Exported, SQLiteError> {
     if rows_buffered > 0 {
         flush_rows(&mut context, true)?;
     }
@@ -323,7 +749,10 @@ pub fn export_table<P: AsRef<Path>>(
         .join()
         .map_err(|_| SQLiteError::Other("Writer thread panicked".to_string()))??;
 
-    Ok(total_rows)
+    Ok(Exported {
+        rows,
+        coerced: context.coerced,
+    })
 }
 
 fn write_batches<P: AsRef<Path>>(
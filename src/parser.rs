@@ -1,10 +1,11 @@
 use std::sync::{Arc, OnceLock};
 use winnow::binary::{be_f64, be_i16, be_i24, be_i32, be_i64, be_i8, be_u16, be_u32, be_u8};
 use winnow::combinator::seq;
-use winnow::error::{ContextError, ParserError};
+use winnow::error::{FromExternalError, ParserError};
 use winnow::prelude::*;
 use winnow::token::{literal, take};
 
+use crate::error::{CorruptionKind, SQLiteError};
 use crate::model::*;
 
 use super::HashMap;
@@ -16,62 +17,206 @@ fn empty_column_types() -> Arc<Vec<SerialType>> {
     Arc::clone(EMPTY_COLUMN_TYPES.get_or_init(|| Arc::new(Vec::with_capacity(0))))
 }
 
-const PAGE_TYPE_INTERIOR_INDEX: u8 = 0x02;
-const PAGE_TYPE_INTERIOR_TABLE: u8 = 0x05;
-const PAGE_TYPE_LEAF_INDEX: u8 = 0x0a;
-const PAGE_TYPE_LEAF_TABLE: u8 = 0x0d;
+pub(crate) const PAGE_TYPE_INTERIOR_INDEX: u8 = 0x02;
+pub(crate) const PAGE_TYPE_INTERIOR_TABLE: u8 = 0x05;
+pub(crate) const PAGE_TYPE_LEAF_INDEX: u8 = 0x0a;
+pub(crate) const PAGE_TYPE_LEAF_TABLE: u8 = 0x0d;
 
-pub(crate) fn db_header<'a, E: ParserError<&'a [u8]>>(input: &mut &'a [u8]) -> Result<DbHeader, E> {
+/// parser error for page contents, can also report what kind of corruption it found
+pub(crate) trait CorruptError<'a>:
+    ParserError<&'a [u8]> + FromExternalError<&'a [u8], CorruptionKind>
+{
+}
+
+impl<'a, E> CorruptError<'a> for E where
+    E: ParserError<&'a [u8]> + FromExternalError<&'a [u8], CorruptionKind>
+{
+}
+
+/// parser error that remembers the address it failed at, so it can be reported as a page offset
+#[derive(Debug)]
+pub(crate) struct ParseFailure {
+    addr: usize,
+    pub(crate) kind: CorruptionKind,
+}
+
+impl<'a> ParserError<&'a [u8]> for ParseFailure {
+    type Inner = Self;
+
+    fn from_input(input: &&'a [u8]) -> Self {
+        ParseFailure {
+            addr: input.as_ptr() as usize,
+            kind: CorruptionKind::Malformed,
+        }
+    }
+
+    fn into_inner(self) -> Result<Self::Inner, Self> {
+        Ok(self)
+    }
+}
+
+impl<'a> FromExternalError<&'a [u8], CorruptionKind> for ParseFailure {
+    fn from_external_error(input: &&'a [u8], kind: CorruptionKind) -> Self {
+        ParseFailure {
+            addr: input.as_ptr() as usize,
+            kind,
+        }
+    }
+}
+
+impl ParseFailure {
+    /// `page` holds the bytes of page `pageno` starting `page_start_offset` bytes into the page
+    pub(crate) fn at_page(self, pageno: u32, page: &[u8], page_start_offset: usize) -> SQLiteError {
+        let offset = self
+            .addr
+            .checked_sub(page.as_ptr() as usize)
+            .filter(|&offset| offset <= page.len())
+            .map_or(0, |offset| offset + page_start_offset);
+
+        SQLiteError::Corrupt {
+            page: pageno,
+            offset,
+            kind: self.kind,
+        }
+    }
+}
+
+/// read a cell pointer and return the bytes of the cell it points at
+#[inline(always)]
+fn cell_pointer<'a, E: CorruptError<'a>>(
+    input: &mut &'a [u8],
+    page_start: &'a [u8],
+    page_start_offset: usize,
+) -> Result<&'a [u8], E> {
+    let pointer_input = *input;
+    let ptr = be_u16.parse_next(input)?;
+
+    (ptr as usize)
+        .checked_sub(page_start_offset)
+        .and_then(|cell_offset| page_start.get(cell_offset..))
+        .filter(|cell_input| !cell_input.is_empty())
+        .ok_or_else(|| {
+            E::from_external_error(&pointer_input, CorruptionKind::CellPointerOutOfRange(ptr))
+        })
+}
+
+pub(crate) fn db_header<'a, E: CorruptError<'a>>(input: &mut &'a [u8]) -> Result<DbHeader, E> {
     literal("SQLite format 3\0").parse_next(input)?;
     let page_size = be_u16.map(PageSize).parse_next(input)?;
-    let _write_version = be_u8.parse_next(input)?;
-    let _read_version = be_u8.parse_next(input)?;
+    let write_version = be_u8.map(FileFormatVersion::from).parse_next(input)?;
+    let read_version = be_u8.map(FileFormatVersion::from).parse_next(input)?;
     let reserved_size = be_u8.parse_next(input)?;
-    let _max_payload_fraction: u8 = be_u8.parse_next(input)?;
-    let _min_payload_fraction = be_u8.parse_next(input)?;
-    let _leaf_payload_fraction = be_u8.parse_next(input)?;
-    let _file_change_counter = be_u32.parse_next(input)?;
-    let _db_size = be_u32.parse_next(input)?;
-    let _first_freelist_page_no = be_u32.parse_next(input)?;
-    let _total_freelist_pages = be_u32.parse_next(input)?;
-    let _schema_cookie = be_u32.parse_next(input)?;
-    let _schema_format_no = be_u32.parse_next(input)?;
-    let _default_page_cache_size = be_u32.parse_next(input)?;
-    let _no_largest_root_b_tree = be_u32.parse_next(input)?;
+    if !page_size.is_valid() || page_size.real_size() - (reserved_size as usize) < 480 {
+        return Err(E::from_external_error(
+            input,
+            CorruptionKind::InvalidPageSize(page_size.real_size()),
+        ));
+    }
+    let max_payload_fraction = be_u8.parse_next(input)?;
+    let min_payload_fraction = be_u8.parse_next(input)?;
+    let leaf_payload_fraction = be_u8.parse_next(input)?;
+    let file_change_counter = be_u32.parse_next(input)?;
+    let db_size = be_u32.parse_next(input)?;
+    let first_freelist_page_no = be_u32.parse_next(input)?;
+    let total_freelist_pages = be_u32.parse_next(input)?;
+    let schema_cookie = be_u32.parse_next(input)?;
+    let schema_format_no = be_u32.parse_next(input)?;
+    let default_page_cache_size = be_u32.parse_next(input)?;
+    let no_largest_root_b_tree = be_u32.parse_next(input)?;
     let db_text_encoding_raw = be_u32.parse_next(input)?;
     let db_text_encoding = db_text_encoding_raw
         .try_into()
         .map_err(|_| E::from_input(input))?;
-    let _user_version = be_u32.parse_next(input)?;
-    let _incremental_vacuum_mode = be_u32.parse_next(input)?;
-    let _application_id = be_u32.parse_next(input)?;
+    let user_version = be_u32.parse_next(input)?;
+    let incremental_vacuum_mode = be_u32.map(|v| v != 0).parse_next(input)?;
+    let application_id = be_u32.parse_next(input)?;
     let _reserved = take(20u8).parse_next(input)?;
-    let _version_valid_for_no = be_u32.parse_next(input)?;
-    let _sqlite_version_number = be_u32.parse_next(input)?;
+    let version_valid_for_no = be_u32.parse_next(input)?;
+    let sqlite_version_number = be_u32.parse_next(input)?;
 
     Ok(DbHeader {
         page_size,
-        // write_version,
-        // read_version,
+        write_version,
+        read_version,
         reserved_size,
-        // max_payload_fraction,
-        // min_payload_fraction,
-        // leaf_payload_fraction,
-        // file_change_counter,
-        // db_size,
-        // first_freelist_page_no,
-        // total_freelist_pages,
-        // schema_cookie,
-        // schema_format_no,
-        // default_page_cache_size,
-        // no_largest_root_b_tree,
+        max_payload_fraction,
+        min_payload_fraction,
+        leaf_payload_fraction,
+        file_change_counter,
+        db_size,
+        first_freelist_page_no,
+        total_freelist_pages,
+        schema_cookie,
+        schema_format_no,
+        default_page_cache_size,
+        no_largest_root_b_tree,
         db_text_encoding,
-        // user_version,
-        // incremental_vacuum_mode,
-        // application_id,
-        // version_valid_for_no,
-        // sqlite_version_number,
+        user_version,
+        incremental_vacuum_mode,
+        application_id,
+        version_valid_for_no,
+        sqlite_version_number,
+    })
+}
+
+pub(crate) const WAL_HEADER_SIZE: usize = 32;
+pub(crate) const WAL_FRAME_HEADER_SIZE: usize = 24;
+
+const WAL_MAGIC_LE: u32 = 0x377f0682;
+const WAL_MAGIC_BE: u32 = 0x377f0683;
+
+pub(crate) fn wal_header<'a, E: ParserError<&'a [u8]>>(
+    input: &mut &'a [u8],
+) -> Result<WalHeader, E> {
+    let header = seq!(WalHeader {
+        magic: be_u32,
+        format_version: be_u32,
+        page_size: be_u32,
+        checkpoint_seq: be_u32,
+        salt1: be_u32,
+        salt2: be_u32,
+        checksum1: be_u32,
+        checksum2: be_u32,
+    })
+    .parse_next(input)?;
+
+    if header.magic != WAL_MAGIC_LE && header.magic != WAL_MAGIC_BE {
+        return Err(E::from_input(input));
+    }
+
+    Ok(header)
+}
+
+pub(crate) fn wal_frame_header<'a, E: ParserError<&'a [u8]>>(
+    input: &mut &'a [u8],
+) -> Result<WalFrameHeader, E> {
+    seq!(WalFrameHeader {
+        pageno: be_u32,
+        db_size: be_u32,
+        salt1: be_u32,
+        salt2: be_u32,
+        checksum1: be_u32,
+        checksum2: be_u32,
     })
+    .parse_next(input)
+}
+
+pub(crate) const JOURNAL_HEADER_SIZE: usize = 28;
+
+const JOURNAL_MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+
+pub(crate) fn journal_header<'a, E: ParserError<&'a [u8]>>(
+    input: &mut &'a [u8],
+) -> Result<JournalHeader, E> {
+    literal(&JOURNAL_MAGIC[..]).parse_next(input)?;
+    seq!(JournalHeader {
+        record_count: be_u32,
+        checksum_nonce: be_u32,
+        db_size: be_u32,
+        sector_size: be_u32,
+        page_size: be_u32,
+    })
+    .parse_next(input)
 }
 
 fn be_i48<'a, E: ParserError<&'a [u8]>>(input: &mut &'a [u8]) -> Result<i64, E> {
@@ -111,32 +256,34 @@ fn be_u64_varint<'a, E: ParserError<&'a [u8]>>(input: &mut &'a [u8]) -> Result<u
     Err(E::from_input(input))
 }
 
-pub(crate) fn page_with_overflow<'a, E: ParserError<&'a [u8]>>(
+pub(crate) fn page_with_overflow<'a, E: CorruptError<'a>>(
     input: &mut &'a [u8],
-    db_header: &'a DbHeader,
+    db_header: &DbHeader,
     page_start_offset: usize,
 ) -> Result<Page<'a>, E> {
     let page_type = input.first().ok_or_else(|| E::from_input(input))?;
 
     match *page_type {
         PAGE_TYPE_INTERIOR_INDEX => {
-            interior_index_b_tree_page(page_start_offset).parse_next(input)?;
-            Ok(Page::InteriorIndex)
+            let page = interior_index_b_tree_page(input, db_header, page_start_offset)?;
+            Ok(Page::InteriorIndex(page))
         }
         PAGE_TYPE_INTERIOR_TABLE => {
             let page = interior_table_b_tree_page(page_start_offset).parse_next(input)?;
             Ok(Page::InteriorTable(page))
         }
         PAGE_TYPE_LEAF_INDEX => {
-            leaf_index_b_tree_page(page_start_offset).parse_next(input)?;
-            Ok(Page::LeafIndex)
+            let page = leaf_index_b_tree_page(input, db_header, page_start_offset)?;
+            Ok(Page::LeafIndex(page))
         }
         PAGE_TYPE_LEAF_TABLE => {
-            let page = leaf_table_b_tree_page_with_overflow(db_header, page_start_offset)
-                .parse_next(input)?;
-            Ok(Page::LeafTable(page))
+            let cells = leaf_table_cells(input, db_header, page_start_offset)?;
+            Ok(Page::LeafTable(LeafTablePage { cells }))
         }
-        _ => Err(E::from_input(input)),
+        page_type => Err(E::from_external_error(
+            input,
+            CorruptionKind::InvalidPageType(page_type),
+        )),
     }
 }
 
@@ -157,31 +304,32 @@ fn leaf_page_header<'a, E: ParserError<&'a [u8]>>(
     input: &mut &'a [u8],
 ) -> Result<LeafPageHeader, E> {
     seq!(LeafPageHeader {
-        _: be_u16, // first_freeblock_offset (unused)
+        first_freeblock_offset: be_u16,
         no_cells: be_u16,
-        _: be_u16, // cell_content_offset (unused)
+        cell_content_offset: be_u16,
         _: be_u8, // no_fragmented_bytes
     })
     .parse_next(input)
 }
 
 #[inline(always)]
-fn interior_index_b_tree_page<'a, E: ParserError<&'a [u8]>>(
+fn interior_index_b_tree_page<'a, E: CorruptError<'a>>(
+    input: &mut &'a [u8],
+    db_header: &DbHeader,
     page_start_offset: usize,
-) -> impl Parser<&'a [u8], (), E> {
-    move |input: &mut &'a [u8]| {
-        let page_start = *input;
-        literal(PAGE_TYPE_INTERIOR_INDEX).parse_next(input)?;
-        let header = interior_page_header.parse_next(input)?;
-
-        for _ in 0..header.no_cells {
-            let ptr = be_u16.parse_next(input)?;
-            let cell_offset = ptr as usize - page_start_offset;
-            let mut cell_input = &page_start[cell_offset..];
-            interior_index_cell.parse_next(&mut cell_input)?;
-        }
-        Ok(())
+) -> Result<InteriorIndexPage<'a>, E> {
+    let page_start = *input;
+    literal(PAGE_TYPE_INTERIOR_INDEX).parse_next(input)?;
+    let header = interior_page_header.parse_next(input)?;
+
+    let mut cells = Vec::with_capacity(header.no_cells as usize);
+    for _ in 0..header.no_cells {
+        let mut cell_input = cell_pointer(input, page_start, page_start_offset)?;
+        let cell = interior_index_cell(&mut cell_input, db_header)?;
+        cells.push(cell);
     }
+
+    Ok(InteriorIndexPage { header, cells })
 }
 
 #[inline(always)]
@@ -198,7 +346,7 @@ fn column_types<'a, E: ParserError<&'a [u8]>>(input: &mut &'a [u8]) -> Result<Ve
 }
 
 #[inline(always)]
-fn column<'a, E: ParserError<&'a [u8]>>(
+fn column<'a, E: CorruptError<'a>>(
     serial_type: &SerialType,
     input: &mut &'a [u8],
 ) -> Result<Option<Payload<'a>>, E> {
@@ -208,19 +356,20 @@ fn column<'a, E: ParserError<&'a [u8]>>(
         SerialType::I16 => Ok(Some(Payload::I64(be_i16.parse_next(input)? as i64))),
         SerialType::I24 => Ok(Some(Payload::I64(be_i24.parse_next(input)? as i64))),
         SerialType::I32 => Ok(Some(Payload::I64(be_i32.parse_next(input)? as i64))),
-        SerialType::I48 => Ok(Some(Payload::I64(be_i48.parse_next(input)? as i64))),
+        SerialType::I48 => Ok(Some(Payload::I64(be_i48.parse_next(input)?))),
         SerialType::I64 => Ok(Some(Payload::I64(be_i64.parse_next(input)?))),
         SerialType::F64 => Ok(Some(Payload::F64(be_f64.parse_next(input)?))),
         SerialType::Const0 => Ok(Some(Payload::I64(0))),
         SerialType::Const1 => Ok(Some(Payload::I64(1))),
-        SerialType::Reserved => unimplemented!("reserved"),
-        SerialType::Blob(_) if serial_type.size() == 0 => Ok(None),
+        SerialType::Reserved => Err(E::from_external_error(
+            input,
+            CorruptionKind::ReservedSerialType,
+        )),
         SerialType::Blob(_) => {
             let size = serial_type.size();
             let data = take(size).parse_next(input)?;
             Ok(Some(Payload::Blob(data)))
         }
-        SerialType::Text(_) if serial_type.size() == 0 => Ok(None),
         SerialType::Text(_) => {
             let size = serial_type.size();
             let data = take(size).parse_next(input)?;
@@ -229,13 +378,65 @@ fn column<'a, E: ParserError<&'a [u8]>>(
     }
 }
 
-fn interior_index_cell<'a, E: ParserError<&'a [u8]>>(input: &mut &'a [u8]) -> Result<(), E> {
-    let _left_child_page_no = be_u32.parse_next(input)?;
+/// decode a whole record (header and every column) from a contiguous payload
+pub(crate) fn record<'a, E: CorruptError<'a>>(
+    input: &mut &'a [u8],
+    column_values: &mut Vec<Option<Payload<'a>>>,
+) -> Result<Vec<SerialType>, E> {
+    let record_start = *input;
+    let header_size = be_u64_varint.parse_next(input)?;
+    let varint_size = record_start.len() - input.len();
+
+    if header_size <= varint_size as u64 || header_size > record_start.len() as u64 {
+        return Err(E::from_external_error(
+            &record_start,
+            CorruptionKind::InvalidHeaderSize(header_size),
+        ));
+    }
+
+    let mut header_input = take(header_size as usize - varint_size).parse_next(input)?;
+    let types = column_types.parse_next(&mut header_input)?;
+
+    column_values.clear();
+    for serial_type in types.iter() {
+        column_values.push(column(serial_type, input)?);
+    }
+
+    Ok(types)
+}
+
+fn index_cell<'a, E: CorruptError<'a>>(
+    input: &mut &'a [u8],
+    db_header: &DbHeader,
+) -> Result<IndexCell<'a>, E> {
     let payload_size = be_u64_varint.parse_next(input)?;
+    let (local_size, overflow_size) = db_header.index_local_and_overflow_size(payload_size);
 
-    // skip the payload we are not interested with the indexes
-    take(payload_size as usize).parse_next(input)?;
-    Ok(())
+    let local_payload = take(local_size).parse_next(input)?;
+    let overflow_page_no = if overflow_size.is_some() {
+        Some(be_u32.parse_next(input)?)
+    } else {
+        None
+    };
+
+    Ok(IndexCell {
+        payload_size,
+        overflow_page_no,
+        local_payload,
+    })
+}
+
+fn interior_index_cell<'a, E: CorruptError<'a>>(
+    input: &mut &'a [u8],
+    db_header: &DbHeader,
+) -> Result<InteriorIndexCell<'a>, E> {
+    let left_child_page_no = be_u32.parse_next(input)?;
+    let cell = index_cell(input, db_header)?;
+
+    Ok(InteriorIndexCell {
+        left_child_page_no,
+        cell,
+    })
 }
 
 fn interior_table_cell<'a, E: ParserError<&'a [u8]>>(
@@ -243,12 +444,12 @@ fn interior_table_cell<'a, E: ParserError<&'a [u8]>>(
 ) -> Result<InteriorCell, E> {
     seq!(InteriorCell {
         left_child_page_no: be_u32,
-        _: be_u64_varint, // integer_key
+        integer_key: be_u64_varint.map(|key| key as i64),
     })
     .parse_next(input)
 }
 
-fn interior_table_b_tree_page<'a, E: ParserError<&'a [u8]>>(
+fn interior_table_b_tree_page<'a, E: CorruptError<'a>>(
     page_start_offset: usize,
 ) -> impl Parser<&'a [u8], InteriorTablePage, E> {
     move |input: &mut &'a [u8]| {
@@ -258,9 +459,7 @@ fn interior_table_b_tree_page<'a, E: ParserError<&'a [u8]>>(
 
         let mut cells = Vec::with_capacity(header.no_cells as usize);
         for _ in 0..header.no_cells {
-            let ptr = be_u16.parse_next(input)?;
-            let cell_offset = ptr as usize - page_start_offset;
-            let mut cell_input = &page_start[cell_offset..];
+            let mut cell_input = cell_pointer(input, page_start, page_start_offset)?;
             let cell = interior_table_cell.parse_next(&mut cell_input)?;
             cells.push(cell);
         }
@@ -269,103 +468,80 @@ fn interior_table_b_tree_page<'a, E: ParserError<&'a [u8]>>(
     }
 }
 
-fn leaf_index_b_tree_page<'a, E: ParserError<&'a [u8]>>(
+fn leaf_index_b_tree_page<'a, E: CorruptError<'a>>(
+    input: &mut &'a [u8],
+    db_header: &DbHeader,
     page_start_offset: usize,
-) -> impl Parser<&'a [u8], (), E> {
-    move |input: &mut &'a [u8]| {
-        let page_start = *input;
-        literal(PAGE_TYPE_LEAF_INDEX).parse_next(input)?;
-        let header = leaf_page_header.parse_next(input)?;
-
-        for _ in 0..header.no_cells {
-            let ptr = be_u16.parse_next(input)?;
-            let cell_offset = ptr as usize - page_start_offset;
-            let mut cell_input = &page_start[cell_offset..];
-            leaf_index_cell.parse_next(&mut cell_input)?;
-        }
-        Ok(())
+) -> Result<LeafIndexPage<'a>, E> {
+    let page_start = *input;
+    literal(PAGE_TYPE_LEAF_INDEX).parse_next(input)?;
+    let header = leaf_page_header.parse_next(input)?;
+
+    let mut cells = Vec::with_capacity(header.no_cells as usize);
+    for _ in 0..header.no_cells {
+        let mut cell_input = cell_pointer(input, page_start, page_start_offset)?;
+        let cell = index_cell(&mut cell_input, db_header)?;
+        cells.push(cell);
     }
-}
 
-fn leaf_index_cell<'a, E: ParserError<&'a [u8]>>(input: &mut &'a [u8]) -> Result<(), E> {
-    let payload_size = be_u64_varint.parse_next(input)?;
-    take(payload_size as usize).parse_next(input)?;
-    Ok(())
+    Ok(LeafIndexPage { cells })
 }
 
-fn leaf_table_b_tree_page_with_overflow<'a, E: ParserError<&'a [u8]>>(
-    db_header: &'a DbHeader,
+/// every cell of a table leaf page, the records are left undecoded
+fn leaf_table_cells<'a, E: CorruptError<'a>>(
+    input: &mut &'a [u8],
+    db_header: &DbHeader,
     page_start_offset: usize,
-) -> impl Parser<&'a [u8], LeafTablePage<'a>, E> {
-    move |input: &mut &'a [u8]| {
-        let page_start = *input;
-        literal(0x0du8).parse_next(input)?;
-        let header = leaf_page_header.parse_next(input)?;
-        let mut cells = Vec::with_capacity(header.no_cells as usize);
-
-        let mut cached_types: HashMap<u64, Arc<Vec<SerialType>>> = HashMap::default();
-
-        for _ in 0..header.no_cells {
-            let ptr = be_u16.parse_next(input)?;
-            let cell_offset = ptr as usize - page_start_offset;
-            let mut cell_input = &page_start[cell_offset..];
-            let mut column_values = Vec::new();
-            let mut cell = leaf_table_cell_with_overflow_cached(
-                &mut cell_input,
-                db_header,
-                &header,
-                &mut cached_types,
-                &mut column_values,
-            )?;
-
-            cell.column_values = Some(column_values.into_iter().collect());
-            cells.push(cell);
-        }
-
-        Ok(LeafTablePage { cells })
+) -> Result<Vec<LeafTableCell<'a>>, E> {
+    let page_start = *input;
+    literal(PAGE_TYPE_LEAF_TABLE).parse_next(input)?;
+    let header = leaf_page_header.parse_next(input)?;
+
+    let mut cells = Vec::with_capacity(header.no_cells as usize);
+    for _ in 0..header.no_cells {
+        let mut cell_input = cell_pointer(input, page_start, page_start_offset)?;
+        cells.push(leaf_table_cell(&mut cell_input, db_header, &header)?);
     }
+
+    Ok(cells)
 }
 
 #[inline(always)]
-fn table_cell_payload_cached<'a, E: ParserError<&'a [u8]>>(
+fn table_cell_payload_cached<'a, E: CorruptError<'a>>(
     input: &mut &'a [u8],
-    max_local: Option<usize>,
     payload_size: u64,
     cached_types: &mut HashMap<u64, Arc<Vec<SerialType>>>,
     column_values: &mut Vec<Option<Payload<'a>>>,
 ) -> Result<TableCellPayload, E> {
+    let record_start = *input;
     let header_size = be_u64_varint.parse_next(input)?;
-
-    if header_size == 1 {
-        return Ok(TableCellPayload {
-            column_types: empty_column_types(),
-        });
+    let varint_size = record_start.len() - input.len();
+
+    // a record has at least one column, its header can't stop at the header size
+    if header_size <= varint_size as u64 || header_size > record_start.len() as u64 {
+        return Err(E::from_external_error(
+            &record_start,
+            CorruptionKind::InvalidHeaderSize(header_size),
+        ));
     }
+    let header_size = header_size as usize;
 
-    let header_bytes = &input[0..header_size as usize - 1];
+    let header_bytes = take(header_size - varint_size).parse_next(input)?;
 
     let header_hash = ahash::RandomState::with_seeds(0, 0, 0, 0).hash_one(header_bytes);
 
     let types = if let Some(cached) = cached_types.get(&header_hash) {
-        *input = &input[header_size as usize - 1..];
         Arc::clone(cached)
     } else {
         let mut header_input = header_bytes;
         let types = column_types.parse_next(&mut header_input)?;
-        *input = &input[header_size as usize - 1..];
         let types_arc = Arc::new(types);
         cached_types.insert(header_hash, Arc::clone(&types_arc));
         types_arc
     };
 
-    let total_payload_size = (payload_size as usize).saturating_sub(header_size as usize);
-
-    let local_data_size = if let Some(max_local) = max_local {
-        let max_payload_bytes = max_local.saturating_sub(header_size as usize);
-        input.len().min(max_payload_bytes).min(total_payload_size)
-    } else {
-        input.len().min(total_payload_size)
-    };
+    let total_payload_size = (payload_size as usize).saturating_sub(header_size);
+    let local_data_size = input.len().min(total_payload_size);
 
     let local_data = &input[..local_data_size];
 
@@ -374,15 +550,13 @@ fn table_cell_payload_cached<'a, E: ParserError<&'a [u8]>>(
 
     for (idx, serial_type) in types.iter().enumerate() {
         let col_size = serial_type.size();
-        if bytes_read + col_size <= local_data_size {
-            let col_data = &local_data[bytes_read..bytes_read + col_size];
-            let mut col_input = col_data;
-            let value = column(serial_type, &mut col_input)?;
-            column_values[idx] = value;
-            bytes_read += col_size;
-        } else {
-            column_values[idx] = None;
-        }
+        let rest = &local_data[bytes_read..];
+        // the header promises more than the payload holds
+        let Some(mut col_input) = rest.get(..col_size) else {
+            return Err(E::from_external_error(&rest, CorruptionKind::Malformed));
+        };
+        column_values[idx] = column(serial_type, &mut col_input)?;
+        bytes_read += col_size;
     }
     *input = &input[local_data_size..];
 
@@ -392,38 +566,72 @@ fn table_cell_payload_cached<'a, E: ParserError<&'a [u8]>>(
     })
 }
 
-fn leaf_table_cell_with_overflow_cached<'a, E: ParserError<&'a [u8]>>(
+/// parse a table leaf cell without decoding its record
+fn leaf_table_cell<'a, E: CorruptError<'a>>(
     input: &mut &'a [u8],
     db_header: &DbHeader,
     page_header: &LeafPageHeader,
-    cached_types: &mut HashMap<u64, Arc<Vec<SerialType>>>,
-    column_values: &mut Vec<Option<Payload<'a>>>,
 ) -> Result<LeafTableCell<'a>, E> {
     let payload_size = be_u64_varint.parse_next(input)?;
     let rowid = be_u64_varint.parse_next(input)?;
 
     let (local_size, overflow_size) = page_header.local_and_overflow_size(db_header, payload_size);
+    let local_payload = take(local_size).parse_next(input)?;
+    let overflow_page_no = if overflow_size.is_some() {
+        Some(be_u32.parse_next(input)?)
+    } else {
+        None
+    };
+
+    Ok(LeafTableCell {
+        payload_size,
+        rowid,
+        payload: TableCellPayload {
+            column_types: empty_column_types(),
+        },
+        overflow_page_no,
+        local_payload,
+    })
+}
+
+/// parse a table leaf cell, the columns are decoded only when the whole payload is local
+///
+/// cells with an overflow chain keep their local payload, the record is decoded once
+/// the chain has been joined
+fn leaf_table_cell_with_overflow_cached<'a, E: CorruptError<'a>>(
+    input: &mut &'a [u8],
+    db_header: &DbHeader,
+    page_header: &LeafPageHeader,
+    cached_types: &mut HashMap<u64, Arc<Vec<SerialType>>>,
+    column_values: &mut Vec<Option<Payload<'a>>>,
+) -> Result<LeafTableCell<'a>, E> {
+    let cell = leaf_table_cell(input, db_header, page_header)?;
+
+    if cell.overflow_page_no.is_some() {
+        column_values.clear();
+        return Ok(cell);
+    }
 
+    let LeafTableCell {
+        payload_size,
+        rowid,
+        local_payload,
+        ..
+    } = cell;
+    let mut payload_input = local_payload;
     let payload = table_cell_payload_cached(
-        input,
-        Some(local_size),
+        &mut payload_input,
         payload_size,
         cached_types,
         column_values,
     )?;
 
-    let overflow_page_no = if overflow_size.is_some() {
-        Some(be_u32.parse_next(input)?)
-    } else {
-        None
-    };
-
     Ok(LeafTableCell {
         payload_size,
         rowid,
         payload,
-        overflow_page_no,
-        column_values: None,
+        overflow_page_no: None,
+        local_payload,
     })
 }
 
@@ -443,118 +651,276 @@ pub(crate) fn overflow_page<'a, E: ParserError<&'a [u8]>>(
     Ok((next_page_no, payload))
 }
 
+/// a freelist trunk page, `max_leaves` is the most leaf page numbers that fit on a page
+pub(crate) fn freelist_trunk_page<'a, E: CorruptError<'a>>(
+    input: &mut &'a [u8],
+    max_leaves: usize,
+) -> Result<FreelistTrunkPage, E> {
+    let next_trunk = be_u32.parse_next(input)?;
+    let count_input = *input;
+    let count = be_u32.parse_next(input)? as usize;
+    if count > max_leaves {
+        return Err(E::from_external_error(
+            &count_input,
+            CorruptionKind::Malformed,
+        ));
+    }
+    let leaves = (0..count)
+        .map(|_| be_u32.parse_next(input))
+        .collect::<Result<Vec<_>, E>>()?;
+
+    Ok(((next_trunk != 0).then_some(next_trunk), leaves))
+}
+
+/// cells left on a table leaf page that is no longer part of a b-tree, `input` starts
+/// `page_start_offset` bytes into the page
+///
+/// nothing is found on a page of any other kind, and cells that don't parse are skipped
+/// instead of failing the page
+pub(crate) fn carve_leaf_table_cells<'a, F>(
+    input: &'a [u8],
+    page_start_offset: usize,
+    db_header: &DbHeader,
+    column_values: &'a mut Vec<Option<Payload<'a>>>,
+    cached_types: &mut HashMap<u64, Arc<Vec<SerialType>>>,
+    mut callback: F,
+) -> Result<(), SQLiteError>
+where
+    F: for<'b> FnMut(
+        LeafTableCell<'a>,
+        &'b mut Vec<Option<Payload<'a>>>,
+    ) -> Result<(), SQLiteError>,
+{
+    let mut input_mut = input;
+    if !matches!(
+        be_u8::<_, ParseFailure>.parse_next(&mut input_mut),
+        Ok(PAGE_TYPE_LEAF_TABLE)
+    ) {
+        return Ok(());
+    }
+    let Ok(header) = leaf_page_header::<ParseFailure>(&mut input_mut) else {
+        return Ok(());
+    };
+
+    for _ in 0..header.no_cells {
+        let Ok(mut cell_input) =
+            cell_pointer::<ParseFailure>(&mut input_mut, input, page_start_offset)
+        else {
+            continue;
+        };
+        let Ok(cell) = leaf_table_cell_with_overflow_cached::<ParseFailure>(
+            &mut cell_input,
+            db_header,
+            &header,
+            cached_types,
+            column_values,
+        ) else {
+            continue;
+        };
+        callback(cell, &mut *column_values)?;
+    }
+
+    Ok(())
+}
+
+/// column count of every record on a table leaf page, read from the record headers alone
+///
+/// the header of a record with an overflow chain is in its local payload, the chain is not
+/// followed, cells that don't parse are skipped
+pub(crate) fn leaf_table_column_counts<F>(
+    input: &[u8],
+    page_start_offset: usize,
+    db_header: &DbHeader,
+    mut f: F,
+) where
+    F: FnMut(usize),
+{
+    let mut input_mut = input;
+    let Ok(Some(header)) = table_leaf_page_header::<ParseFailure>(&mut input_mut) else {
+        return;
+    };
+
+    for _ in 0..header.no_cells {
+        let Ok(mut cell_input) =
+            cell_pointer::<ParseFailure>(&mut input_mut, input, page_start_offset)
+        else {
+            continue;
+        };
+        let Ok(cell) = leaf_table_cell::<ParseFailure>(&mut cell_input, db_header, &header) else {
+            continue;
+        };
+        let mut payload = cell.local_payload;
+        let Ok(header_size) = be_u64_varint::<ParseFailure>.parse_next(&mut payload) else {
+            continue;
+        };
+        let varint_size = cell.local_payload.len() - payload.len();
+        let Some(types_size) = (header_size as usize).checked_sub(varint_size) else {
+            continue;
+        };
+        let Ok(mut types) = take::<_, _, ParseFailure>(types_size).parse_next(&mut payload) else {
+            continue;
+        };
+        if let Ok(types) = column_types::<ParseFailure>(&mut types) {
+            f(types.len());
+        }
+    }
+}
+
+/// header of a table leaf page, `None` for a page of any other kind
+pub(crate) fn table_leaf_page_header<'a, E: ParserError<&'a [u8]>>(
+    input: &mut &'a [u8],
+) -> Result<Option<LeafPageHeader>, E> {
+    if be_u8.parse_next(input)? != PAGE_TYPE_LEAF_TABLE {
+        return Ok(None);
+    }
+    leaf_page_header.map(Some).parse_next(input)
+}
+
+/// whether `page` starts like a b-tree page of its length, with the cell pointer array,
+/// the cell content area and the freeblocks all inside it
+pub(crate) fn plausible_btree_page(page: &[u8]) -> bool {
+    let mut input = page;
+    let Ok(page_type) = be_u8::<_, ParseFailure>.parse_next(&mut input) else {
+        return false;
+    };
+    let header_size = match page_type {
+        PAGE_TYPE_INTERIOR_INDEX | PAGE_TYPE_INTERIOR_TABLE => 12,
+        PAGE_TYPE_LEAF_INDEX | PAGE_TYPE_LEAF_TABLE => 8,
+        _ => return false,
+    };
+    let Ok(header) = leaf_page_header::<ParseFailure>(&mut input) else {
+        return false;
+    };
+
+    let content_start = match header.cell_content_offset {
+        0 => 0x1_00_00,
+        offset => offset as usize,
+    };
+    let first_freeblock = header.first_freeblock_offset as usize;
+    let pointers_end = header_size + 2 * header.no_cells as usize;
+    if pointers_end > content_start
+        || content_start > page.len()
+        || (first_freeblock != 0 && !(content_start..page.len()).contains(&first_freeblock))
+    {
+        return false;
+    }
+
+    page[header_size..pointers_end]
+        .chunks_exact(2)
+        .all(|pointer| {
+            let pointer = u16::from_be_bytes([pointer[0], pointer[1]]) as usize;
+            (content_start..page.len()).contains(&pointer)
+        })
+}
+
+/// offset of the next freeblock and the size of this one
+pub(crate) fn freeblock<'a, E: ParserError<&'a [u8]>>(
+    input: &mut &'a [u8],
+) -> Result<(u16, u16), E> {
+    (be_u16, be_u16).parse_next(input)
+}
+
+/// payload size and rowid in front of the record of a table leaf cell
+pub(crate) fn table_cell_header<'a, E: ParserError<&'a [u8]>>(
+    input: &mut &'a [u8],
+) -> Result<(u64, u64), E> {
+    (be_u64_varint, be_u64_varint).parse_next(input)
+}
+
+/// bytes taken by the `count` serial types at the start of `input`, and by the values they
+/// describe, an error when the values would not fit in memory
+pub(crate) fn serial_types_size<'a, E: ParserError<&'a [u8]>>(
+    input: &mut &'a [u8],
+    count: usize,
+) -> Result<(usize, usize), E> {
+    let start = input.len();
+    let mut body_size: usize = 0;
+    for _ in 0..count {
+        let size = SerialType::from(be_u64_varint.parse_next(input)?).size();
+        body_size = body_size
+            .checked_add(size)
+            .ok_or_else(|| E::from_input(input))?;
+    }
+    Ok((start - input.len(), body_size))
+}
+
 pub(crate) enum CellType<'a, 'b> {
-    LeafTable(LeafTableCell<'a>, &'b Vec<Option<Payload<'a>>>), // cell + column values reference
+    LeafTable(LeafTableCell<'a>, &'b mut Vec<Option<Payload<'a>>>), // cell + column values reference
     // LeafIndex,
-    InteriorTable(u32),          // page number
+    InteriorTable(u32, i64),     // page number, integer key
     InteriorTableRightmost(u32), // rightmost pointer
 }
 
 pub(crate) fn stream_page_cells<'a, F>(
     input: &'a [u8],
+    pageno: u32,
     db_header: &DbHeader,
     page_start_offset: usize,
     column_values: &'a mut Vec<Option<Payload<'a>>>,
     cached_types: &mut HashMap<u64, Arc<Vec<SerialType>>>,
     mut callback: F,
-) -> Result<(), crate::error::SQLiteError>
+) -> Result<(), SQLiteError>
 where
     F: for<'b> FnMut(
         CellType<'a, 'b>,
         &mut HashMap<u64, Arc<Vec<SerialType>>>,
-    ) -> Result<(), crate::error::SQLiteError>,
+    ) -> Result<(), SQLiteError>,
 {
     let mut input_mut = input;
     let page_start = input;
+    let corrupt = |err: ParseFailure| err.at_page(pageno, page_start, page_start_offset);
 
-    let page_type = *input
-        .first()
-        .ok_or_else(|| crate::error::SQLiteError::Other("Empty page".into()))?;
-    literal(page_type)
+    let page_type = be_u8::<_, ParseFailure>
         .parse_next(&mut input_mut)
-        .map_err(|_: ContextError| crate::error::SQLiteError::Other("Invalid page type".into()))?;
+        .map_err(corrupt)?;
 
     match page_type {
         PAGE_TYPE_LEAF_TABLE => {
-            let header = leaf_page_header::<ContextError>(&mut input_mut).map_err(|_| {
-                crate::error::SQLiteError::Other("Failed to parse leaf header".into())
-            })?;
-
-            for _ in 0..header.no_cells {
-                let ptr = be_u16
-                    .parse_next(&mut input_mut)
-                    .map_err(|_: ContextError| {
-                        crate::error::SQLiteError::Other("Failed to parse cell pointer".into())
-                    })?;
-                let cell_offset = ptr as usize - page_start_offset;
-                let mut cell_input = &page_start[cell_offset..];
-
-                let cell = {
-                    leaf_table_cell_with_overflow_cached(
-                        &mut cell_input,
-                        db_header,
-                        &header,
-                        cached_types,
-                        column_values,
-                    )
-                    .map_err(|_: ContextError| {
-                        crate::error::SQLiteError::Other("Failed to parse cell".into())
-                    })?
-                };
-                callback(CellType::LeafTable(cell, &*column_values), cached_types)?;
-            }
-        }
-        PAGE_TYPE_LEAF_INDEX => {
-            let header = leaf_page_header::<ContextError>(&mut input_mut).map_err(|_| {
-                crate::error::SQLiteError::Other("Failed to parse leaf index header".into())
-            })?;
+            let header = leaf_page_header::<ParseFailure>(&mut input_mut).map_err(corrupt)?;
 
             for _ in 0..header.no_cells {
-                let ptr = be_u16
-                    .parse_next(&mut input_mut)
-                    .map_err(|_: ContextError| {
-                        crate::error::SQLiteError::Other("Failed to parse cell pointer".into())
-                    })?;
-                let cell_offset = ptr as usize - page_start_offset;
-                let mut cell_input = &page_start[cell_offset..];
-                leaf_index_cell::<ContextError>(&mut cell_input).map_err(|_: ContextError| {
-                    crate::error::SQLiteError::Other("Failed to parse index cell".into())
-                })?;
+                let mut cell_input =
+                    cell_pointer::<ParseFailure>(&mut input_mut, page_start, page_start_offset)
+                        .map_err(corrupt)?;
+
+                let cell = leaf_table_cell_with_overflow_cached::<ParseFailure>(
+                    &mut cell_input,
+                    db_header,
+                    &header,
+                    cached_types,
+                    column_values,
+                )
+                .map_err(corrupt)?;
+                callback(CellType::LeafTable(cell, &mut *column_values), cached_types)?;
             }
         }
         PAGE_TYPE_INTERIOR_TABLE => {
-            let header = interior_page_header::<ContextError>(&mut input_mut).map_err(|_| {
-                crate::error::SQLiteError::Other("Failed to parse interior header".into())
-            })?;
+            let header = interior_page_header::<ParseFailure>(&mut input_mut).map_err(corrupt)?;
 
             for _ in 0..header.no_cells {
-                let ptr = be_u16
-                    .parse_next(&mut input_mut)
-                    .map_err(|_: ContextError| {
-                        crate::error::SQLiteError::Other("Failed to parse cell pointer".into())
-                    })?;
-                let cell_offset = ptr as usize - page_start_offset;
-                let mut cell_input = &page_start[cell_offset..];
-                let cell = interior_table_cell::<ContextError>(&mut cell_input)
-                    .map_err(|_| crate::error::SQLiteError::Other("Failed to parse cell".into()))?;
+                let mut cell_input =
+                    cell_pointer::<ParseFailure>(&mut input_mut, page_start, page_start_offset)
+                        .map_err(corrupt)?;
+                let cell = interior_table_cell::<ParseFailure>(&mut cell_input).map_err(corrupt)?;
 
                 callback(
-                    CellType::InteriorTable(cell.left_child_page_no),
+                    CellType::InteriorTable(cell.left_child_page_no, cell.integer_key),
                     cached_types,
                 )?;
             }
 
-            if header.rightmost_pointer > 0 {
-                callback(
-                    CellType::InteriorTableRightmost(header.rightmost_pointer),
-                    cached_types,
-                )?;
-            }
+            callback(
+                CellType::InteriorTableRightmost(header.rightmost_pointer),
+                cached_types,
+            )?;
         }
-        _ => {
-            return Err(crate::error::SQLiteError::Other(
-                "Unsupported page type for streaming".into(),
-            ));
+        // an index page, or no b-tree page at all, in a table b-tree
+        page_type => {
+            return Err(SQLiteError::Corrupt {
+                page: pageno,
+                offset: page_start_offset,
+                kind: CorruptionKind::InvalidPageType(page_type),
+            });
         }
     }
 
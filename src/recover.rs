@@ -0,0 +1,541 @@
+use std::ops::Range;
+
+use crate::catalog::ObjectKind;
+use crate::error;
+use crate::model::{self, Affinity, Page, Payload, RawText, TextEncoding};
+use crate::parser::{
+    self, freeblock, serial_types_size, table_cell_header, table_leaf_page_header, ParseFailure,
+};
+use crate::{Pager, Reader, SqlSchema, TreeWalk, HEADER_SIZE, SQLITE_SCHEMA_SQL};
+
+/// part of a table leaf page that holds no live cell
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FreeSpace {
+    /// a freed cell, its first 4 bytes are overwritten by the freeblock header
+    Freeblock,
+    /// the gap between the cell pointer array and the cell content area
+    Unallocated,
+}
+
+/// a deleted record found in the free space of a live table leaf page
+#[derive(Clone, Debug)]
+pub struct RecoveredRecord<'a> {
+    /// table the page belongs to
+    pub table: &'a str,
+    pub pageno: u32,
+    /// offset of the record header within the page
+    pub offset: usize,
+    pub found_in: FreeSpace,
+    /// `None` when the payload size and rowid in front of the record were overwritten
+    pub rowid: Option<i64>,
+    /// from 0 to 1, how much the record looks like a row of the table rather than leftover
+    /// bytes that happen to decode
+    pub confidence: f32,
+}
+
+impl<P: Pager> Reader<P> {
+    /// deleted records left in the freeblocks and unallocated space of the table leaf pages
+    /// of every rowid table, sqlite_schema included
+    ///
+    /// records that are not intact anymore are rebuilt where the table tells what is
+    /// missing, and skipped otherwise
+    pub fn recover_deleted_records<F>(&self, mut callback: F) -> error::Result<()>
+    where
+        F: FnMut(&RecoveredRecord<'_>, &Vec<Option<Payload<'_>>>) -> error::Result<()>,
+    {
+        let schema_table = SqlSchema::try_from(SQLITE_SCHEMA_SQL.to_owned())?;
+        let mut tables = vec![("sqlite_schema", 1, Some(&schema_table))];
+        tables.extend(
+            self.catalog()?
+                .iter()
+                .filter(|object| object.kind == ObjectKind::Table && object.root_page != 0)
+                .filter(|object| !object.table_schema().is_some_and(|s| s.without_rowid))
+                .map(|object| {
+                    (
+                        object.name.as_str(),
+                        object.root_page,
+                        object.table_schema(),
+                    )
+                }),
+        );
+
+        let usable_size = self.header.usable_page_size();
+        for (table, root_page, schema) in tables {
+            let recovery = Recovery::new(schema, self.header.db_text_encoding);
+            let mut leaves = Vec::new();
+            self.table_leaf_pages(root_page, &mut TreeWalk::default(), &mut leaves)?;
+
+            for pageno in leaves {
+                let page_bytes = self.page_bytes(pageno)?;
+                let page = &page_bytes[..usable_size];
+
+                for (found_in, range) in free_space(page, pageno) {
+                    recovery.scan(
+                        page,
+                        found_in,
+                        range,
+                        &mut |offset, rowid, confidence, values| {
+                            let record = RecoveredRecord {
+                                table,
+                                pageno,
+                                offset,
+                                found_in,
+                                rowid,
+                                confidence,
+                            };
+                            callback(&record, values)
+                        },
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// table leaf pages of the b-tree rooted at `pageno`, left to right
+    fn table_leaf_pages(
+        &self,
+        pageno: u32,
+        walk: &mut TreeWalk,
+        leaves: &mut Vec<u32>,
+    ) -> error::Result<()> {
+        walk.enter(pageno)?;
+        let page_bytes = self.page_bytes(pageno)?;
+
+        match self.parse_page(pageno, &page_bytes)? {
+            Page::InteriorTable(page) => {
+                let children = page.cells.iter().map(|cell| cell.left_child_page_no);
+                for child in children.chain(Some(page.header.rightmost_pointer)) {
+                    self.table_leaf_pages(child, walk, leaves)?;
+                }
+            }
+            Page::LeafTable(_) => leaves.push(pageno),
+            Page::InteriorIndex(_) | Page::LeafIndex(_) => {}
+        }
+
+        walk.leave();
+        Ok(())
+    }
+}
+
+/// freeblocks and unallocated space of a table leaf page, nothing for other pages
+fn free_space(page: &[u8], pageno: u32) -> Vec<(FreeSpace, Range<usize>)> {
+    let header_offset = if pageno == 1 { HEADER_SIZE } else { 0 };
+    let mut input = &page[header_offset..];
+    let Ok(Some(header)) = table_leaf_page_header::<ParseFailure>(&mut input) else {
+        return Vec::new();
+    };
+
+    let mut regions = Vec::new();
+    let pointers_end = header_offset + 8 + 2 * header.no_cells as usize;
+    let content_start = match header.cell_content_offset {
+        0 => 65536,
+        offset => offset as usize,
+    }
+    .min(page.len());
+    // the cell pointer array shrinks without clearing the pointers of deleted cells, they
+    // point into the content area
+    let mut unallocated_start = pointers_end;
+    while let Some(pointer) = page.get(unallocated_start..unallocated_start + 2) {
+        let pointer = u16::from_be_bytes([pointer[0], pointer[1]]) as usize;
+        if unallocated_start + 2 > content_start || !(content_start..page.len()).contains(&pointer)
+        {
+            break;
+        }
+        unallocated_start += 2;
+    }
+    if unallocated_start < content_start {
+        regions.push((FreeSpace::Unallocated, unallocated_start..content_start));
+    }
+
+    // freeblocks are chained in ascending order inside the content area, anything else
+    // ends the chain
+    let mut offset = header.first_freeblock_offset as usize;
+    let mut min_offset = content_start;
+    while offset != 0 && offset >= min_offset && offset + 4 <= page.len() {
+        let mut input = &page[offset..];
+        let Ok((next, size)) = freeblock::<ParseFailure>(&mut input) else {
+            break;
+        };
+        let end = offset + size as usize;
+        if size < 4 || end > page.len() {
+            break;
+        }
+
+        regions.push((FreeSpace::Freeblock, offset..end));
+        min_offset = end;
+        offset = next as usize;
+    }
+
+    regions
+}
+
+/// what is known about the table a page belongs to
+struct Recovery<'s> {
+    schema: Option<&'s SqlSchema>,
+    affinities: Vec<Affinity>,
+    text_encoding: TextEncoding,
+}
+
+impl<'s> Recovery<'s> {
+    fn new(schema: Option<&'s SqlSchema>, text_encoding: TextEncoding) -> Self {
+        Recovery {
+            schema,
+            affinities: schema.map_or_else(Vec::new, SqlSchema::column_affinities),
+            text_encoding,
+        }
+    }
+
+    /// hand every record found in `range` of `page` to `emit`, with its offset, rowid and
+    /// confidence
+    fn scan<F>(
+        &self,
+        page: &[u8],
+        found_in: FreeSpace,
+        range: Range<usize>,
+        emit: &mut F,
+    ) -> error::Result<()>
+    where
+        F: FnMut(usize, Option<i64>, f32, &Vec<Option<Payload<'_>>>) -> error::Result<()>,
+    {
+        let mut data_start = range.start;
+        let mut end = range.end;
+
+        match found_in {
+            FreeSpace::Freeblock => data_start = self.scan_freed_cells(page, range, emit)?,
+            // a cell freed at the start of the content area grows the unallocated space
+            // instead of becoming a freeblock, it gets a freeblock header all the same
+            FreeSpace::Unallocated => {
+                if let Some(start) = self.freed_at_content_start(page, &range) {
+                    self.scan_freed_cells(page, start..range.end, emit)?;
+                    end = start;
+                }
+            }
+        }
+
+        let mut offset = data_start;
+        while offset < end {
+            // a whole cell, or a record whose payload size and rowid may be overwritten
+            let found = match self.cell(page, offset, end) {
+                Some((record_start, rowid, len, values)) => {
+                    Some((record_start, Some(rowid), len, values))
+                }
+                None => self.intact_record(page, offset, end).map(|(len, values)| {
+                    (
+                        offset,
+                        cell_rowid(page, data_start, offset, len),
+                        len,
+                        values,
+                    )
+                }),
+            };
+            let Some((record_start, rowid, len, mut values)) = found else {
+                offset += 1;
+                continue;
+            };
+
+            if let (Some(schema), Some(rowid)) = (self.schema, rowid) {
+                schema.complete_row(rowid, &mut values);
+            }
+            let base = if rowid.is_some() { 0.7 } else { 0.5 };
+            emit(record_start, rowid, self.confidence(&values, base), &values)?;
+            offset = record_start + len;
+        }
+
+        Ok(())
+    }
+
+    /// hand the records of the freed cells in `range` to `emit`, returns where the bytes
+    /// they left off begin
+    ///
+    /// the freeblock header took the place of the first 4 bytes of the cell, a cell freed
+    /// earlier that merged with it keeps a header of its own, with its size before the merge
+    fn scan_freed_cells<F>(
+        &self,
+        page: &[u8],
+        range: Range<usize>,
+        emit: &mut F,
+    ) -> error::Result<usize>
+    where
+        F: FnMut(usize, Option<i64>, f32, &Vec<Option<Payload<'_>>>) -> error::Result<()>,
+    {
+        let mut cell = range.clone();
+        let mut data_start = range.start + 4;
+
+        while let Some(lost) = self.lost_header_record(page, &cell) {
+            let mut input = lost.record.as_slice();
+            let mut values = Vec::new();
+            if parser::record::<ParseFailure>(&mut input, &mut values).is_err() {
+                break;
+            }
+            emit(lost.offset, None, lost.confidence, &values)?;
+            data_start = lost.end;
+
+            match freed_cell(page, lost.end, range.end) {
+                Some(next) if self.cell(page, lost.end, range.end).is_none() => {
+                    data_start = next.start + 4;
+                    cell = next;
+                }
+                _ => break,
+            }
+        }
+
+        Ok(data_start)
+    }
+
+    /// where the cells freed at the start of the content area begin, each ends where the
+    /// one freed before it begins, and the first one freed at the end of `range`
+    fn freed_at_content_start(&self, page: &[u8], range: &Range<usize>) -> Option<usize> {
+        let mut start = None;
+        let mut end = range.end;
+
+        while let Some(cell_start) = (range.start..end.saturating_sub(4)).rev().find(|&offset| {
+            freed_cell(page, offset, end).is_some_and(|cell| cell.end == end)
+                && self
+                    .lost_header_record(page, &(offset..end))
+                    .is_some_and(|lost| lost.end == end)
+        }) {
+            start = Some(cell_start);
+            end = cell_start;
+        }
+
+        start
+    }
+
+    /// a whole record starting at `offset` and ending before `end`, with its length
+    fn intact_record<'p>(
+        &self,
+        page: &'p [u8],
+        offset: usize,
+        end: usize,
+    ) -> Option<(usize, Vec<Option<Payload<'p>>>)> {
+        let mut input = &page[offset..end];
+        let mut values = Vec::new();
+        let types = parser::record::<ParseFailure>(&mut input, &mut values).ok()?;
+        let len = end - offset - input.len();
+        self.plausible(&types, &page[offset..offset + len])
+            .then_some((len, values))
+    }
+
+    /// a record holds some content other than zeroes, which is what secure delete and
+    /// fresh pages leave, no more columns than its table and NULL for its rowid alias
+    fn plausible(&self, types: &[model::SerialType], record: &[u8]) -> bool {
+        let body_size: usize = types.iter().map(model::SerialType::size).sum();
+        !types.is_empty()
+            && record[record.len() - body_size..]
+                .iter()
+                .any(|&byte| byte != 0)
+            && self.schema.is_none_or(|schema| {
+                types.len() <= schema.columns.len()
+                    && schema
+                        .rowid_alias
+                        .and_then(|alias| types.get(alias))
+                        .is_none_or(|serial_type| matches!(serial_type, model::SerialType::Null))
+            })
+    }
+
+    /// the record of a freed cell whose header size, and maybe first serial type, the
+    /// freeblock header overwrote, rebuilt from the column count of the table
+    ///
+    /// a cell starts with its payload size and rowid, for small rows they take 2 or 3 bytes
+    /// and the record header follows
+    fn lost_header_record(&self, page: &[u8], range: &Range<usize>) -> Option<LostHeader> {
+        let schema = self.schema?;
+        let columns = schema.columns.len();
+        let data = &page[range.start + 4..range.end];
+
+        // record header 3 bytes in, only its size is lost
+        let mut candidates = vec![self.rebuild(page, range, 3, None)];
+
+        // record header 2 bytes in, the first serial type is lost too, a rowid alias stores
+        // NULL and otherwise the type follows from what is left of the freeblock, which
+        // only works when the freeblock is a single cell
+        let mut input = data;
+        let lost_type = match schema.rowid_alias {
+            Some(0) => Some(0),
+            _ => serial_types_size::<ParseFailure>(&mut input, columns - 1)
+                .ok()
+                .and_then(|(types_size, body_size)| {
+                    let used = 4usize.checked_add(types_size)?.checked_add(body_size)?;
+                    let first_size = range.len().checked_sub(used)?;
+                    lost_serial_type(*self.affinities.first()?, first_size)
+                }),
+        };
+        if lost_type.is_some() {
+            candidates.push(self.rebuild(page, range, 2, lost_type));
+        }
+
+        candidates.into_iter().flatten().max_by(|a, b| {
+            (a.fits, a.confidence)
+                .partial_cmp(&(b.fits, b.confidence))
+                .unwrap()
+        })
+    }
+
+    /// rebuild the record of a freed cell with its header `header_offset` bytes in, with
+    /// `lost_type` in place of an overwritten first serial type
+    fn rebuild(
+        &self,
+        page: &[u8],
+        range: &Range<usize>,
+        header_offset: usize,
+        lost_type: Option<u8>,
+    ) -> Option<LostHeader> {
+        let schema = self.schema?;
+        let data = &page[range.start + 4..range.end];
+        let mut input = data;
+        let surviving = schema.columns.len() - lost_type.is_some() as usize;
+        let (types_size, _) = serial_types_size::<ParseFailure>(&mut input, surviving).ok()?;
+        let header_size = 1 + lost_type.is_some() as usize + types_size;
+        if header_size >= 0x80 {
+            return None;
+        }
+
+        let mut record = vec![header_size as u8];
+        record.extend(lost_type);
+        let prefix = record.len();
+        record.extend_from_slice(data);
+
+        let mut input = record.as_slice();
+        let mut values = Vec::new();
+        let types = parser::record::<ParseFailure>(&mut input, &mut values).ok()?;
+        let record_len = record.len() - input.len();
+        if !self.plausible(&types, &record[..record_len]) {
+            return None;
+        }
+        let end = range.start + 4 + record_len - prefix;
+
+        // the freeblock is exactly as large as the cell, or it merged with the cells after
+        // it and the next one starts right where this record ends
+        let fits = end == range.end
+            || self.cell(page, end, range.end).is_some()
+            || freed_cell(page, end, range.end).is_some();
+        let base = match (fits, lost_type) {
+            (true, Some(0) | None) => 0.5,
+            (true, Some(_)) => 0.4,
+            (false, _) => 0.3,
+        };
+        let confidence = self.confidence(&values, base);
+        record.truncate(record_len);
+
+        Some(LostHeader {
+            offset: range.start + header_offset,
+            end,
+            fits,
+            confidence,
+            record,
+        })
+    }
+
+    /// a whole cell starting at `offset`, with where its record starts, its rowid, and the
+    /// length and values of the record
+    fn cell<'p>(
+        &self,
+        page: &'p [u8],
+        offset: usize,
+        end: usize,
+    ) -> Option<(usize, i64, usize, Vec<Option<Payload<'p>>>)> {
+        let mut input = &page[offset..end];
+        let (payload_size, rowid) = table_cell_header::<ParseFailure>(&mut input).ok()?;
+        let record_start = end - input.len();
+        let (len, values) = self.intact_record(page, record_start, end)?;
+        (len as u64 == payload_size).then_some((record_start, rowid as i64, len, values))
+    }
+
+    /// start from `base` and adjust for how well `values` fit the table
+    fn confidence(&self, values: &[Option<Payload<'_>>], base: f32) -> f32 {
+        let mut confidence = base;
+
+        if let Some(schema) = self.schema {
+            // rows written before ALTER TABLE ADD COLUMN are shorter
+            if values.len() == schema.columns.len() {
+                confidence += 0.2;
+            }
+        }
+
+        for (idx, value) in values.iter().enumerate() {
+            if let Some(&affinity) = self.affinities.get(idx) {
+                if !fits_affinity(affinity, value, self.text_encoding) {
+                    confidence -= 0.15;
+                }
+            }
+            if let Some(Payload::Text(text)) = value {
+                if !plausible_text(text, self.text_encoding) {
+                    confidence -= 0.2;
+                }
+            }
+        }
+
+        confidence.clamp(0.0, 1.0)
+    }
+}
+
+/// a record rebuilt by [`Recovery::lost_header_record`]
+struct LostHeader {
+    /// where the record header began
+    offset: usize,
+    /// end of the record within the page
+    end: usize,
+    /// the record fills the freeblock, or the next freed cell follows it
+    fits: bool,
+    confidence: f32,
+    record: Vec<u8>,
+}
+
+/// the cell freed before the one in front of it, by the freeblock header it got then
+fn freed_cell(page: &[u8], offset: usize, end: usize) -> Option<Range<usize>> {
+    let mut input = page.get(offset..end)?;
+    let (_, size) = freeblock::<ParseFailure>(&mut input).ok()?;
+    let cell_end = offset + size as usize;
+    (size >= 4 && cell_end <= end).then_some(offset..cell_end)
+}
+
+/// the rowid of a record whose payload size and rowid in front of it survived
+fn cell_rowid(page: &[u8], data_start: usize, offset: usize, record_len: usize) -> Option<i64> {
+    // the two varints take 2 to 18 bytes
+    (offset.saturating_sub(18).max(data_start)..offset.saturating_sub(1)).find_map(|start| {
+        let mut input = &page[start..offset];
+        let (payload_size, rowid) = table_cell_header::<ParseFailure>(&mut input).ok()?;
+        (input.is_empty() && payload_size == record_len as u64).then_some(rowid as i64)
+    })
+}
+
+/// the serial type a column of this affinity uses for a value of `size` bytes, `None` when
+/// that can't be told
+fn lost_serial_type(affinity: Affinity, size: usize) -> Option<u8> {
+    let serial_type = match (affinity, size) {
+        (Affinity::Text | Affinity::Blob, size) => 13 + 2 * size,
+        (Affinity::Real, 8) => 7,
+        (Affinity::Integer | Affinity::Real | Affinity::Numeric, size) => match size {
+            1..=4 => size,
+            6 => 5,
+            8 => 6,
+            _ => return None,
+        },
+    };
+    // only a type that fits in the single byte left for it
+    u8::try_from(serial_type)
+        .ok()
+        .filter(|&serial_type| serial_type < 0x80)
+}
+
+/// whether SQLite could have stored `value` in a column of this affinity
+fn fits_affinity(affinity: Affinity, value: &Option<Payload<'_>>, encoding: TextEncoding) -> bool {
+    match (affinity, value) {
+        // numbers are converted to text
+        (Affinity::Text, Some(Payload::I64(_) | Payload::F64(_))) => false,
+        // text that looks like a number is converted to one
+        (Affinity::Integer | Affinity::Real | Affinity::Numeric, Some(Payload::Text(text))) => {
+            text.decode(encoding).trim().parse::<f64>().is_err()
+        }
+        _ => true,
+    }
+}
+
+/// text that decodes without replacement and control characters
+fn plausible_text(text: &RawText<'_>, encoding: TextEncoding) -> bool {
+    !text.decode(encoding).chars().any(|c| {
+        c == char::REPLACEMENT_CHARACTER || (c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+    })
+}
@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use crate::catalog::{ObjectKind, SchemaObject};
+use crate::error::{self, CorruptionKind, SQLiteError};
+use crate::model::{DbHeader, Page, Payload, SerialType};
+use crate::parser::{
+    db_header, leaf_table_column_counts, plausible_btree_page, ParseFailure,
+    PAGE_TYPE_INTERIOR_INDEX, PAGE_TYPE_INTERIOR_TABLE, PAGE_TYPE_LEAF_INDEX, PAGE_TYPE_LEAF_TABLE,
+};
+use crate::{
+    HashMap, HashSet, Pager, Reader, SqlSchema, HEADER_SIZE, MAX_TREE_DEPTH, SQLITE_SCHEMA_SQL,
+};
+
+/// pages after the first looked at for each page size when the header is lost
+const SAMPLE_PAGES: u32 = 256;
+
+/// SQLite's default page size, taken when no page size stands out
+const DEFAULT_PAGE_SIZE: usize = 4096;
+
+/// what a page holds, by its type byte
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PageKind {
+    InteriorIndex,
+    InteriorTable,
+    LeafIndex,
+    LeafTable,
+    /// an overflow, freelist or pointer map page, or a b-tree page with a damaged type byte
+    Other,
+}
+
+/// a row read from a table leaf page by [`Reader::salvage`]
+#[derive(Clone, Debug)]
+pub struct SalvagedRow<'a> {
+    /// table the leaf page is reachable from, `None` for a row of lost_and_found
+    pub table: Option<&'a str>,
+    /// top of the b-tree the leaf page hangs from, the root page of its table when it has one
+    pub root_page: u32,
+    pub pageno: u32,
+    /// most common column count of the records on the page, lost pages under one
+    /// `root_page` with the same shape most likely come from one table
+    pub shape: usize,
+    pub rowid: i64,
+}
+
+impl<P: Pager> Reader<P> {
+    /// like [`Reader::from_source`], but a database header that doesn't parse is replaced
+    /// by one with the page size guessed from the b-tree pages and SQLite's defaults for
+    /// everything else, for [`Reader::salvage`]
+    pub fn salvage_source(pager: P) -> error::Result<Reader<P>> {
+        let header = match pager.page(1, HEADER_SIZE)? {
+            Some(first_page) => db_header::<ParseFailure>(&mut &*first_page).ok(),
+            None => None,
+        };
+        let header = match header {
+            Some(header) => header,
+            None => DbHeader::with_page_size(guess_page_size(&pager)?),
+        };
+        Ok(Reader::new(pager, header))
+    }
+
+    /// kind of every page of the database by its type byte, page 1 first
+    pub fn page_kinds(&self) -> error::Result<Vec<PageKind>> {
+        let mut kinds = Vec::new();
+        self.scan_pages(|pageno, page| {
+            kinds.push(page_kind(pageno, page));
+            Ok(())
+        })?;
+        Ok(kinds)
+    }
+
+    /// every row still readable on a table leaf page, without trusting the schema tree
+    ///
+    /// each leaf page goes to the table whose root page it is reachable from through the
+    /// interior pages that parse, or to lost_and_found when there is none, that includes
+    /// deleted rows on free pages. The schema comes from the leaf pages holding
+    /// sqlite_schema rows when its tree is damaged. Index and WITHOUT ROWID pages are not
+    /// read, cells and overflow chains that don't decode are skipped
+    pub fn salvage<F>(&self, mut callback: F) -> error::Result<()>
+    where
+        F: FnMut(&SalvagedRow<'_>, &Vec<Option<Payload<'_>>>) -> error::Result<()>,
+    {
+        // the interior pages that point at each page, a stale one may point at a page that
+        // moved on to another b-tree
+        let mut parents: HashMap<u32, Vec<u32>> = HashMap::default();
+        let mut leaves = Vec::new();
+        self.scan_pages(|pageno, page| {
+            match page_kind(pageno, page) {
+                PageKind::InteriorTable => {
+                    if let Ok(Page::InteriorTable(interior)) = self.parse_page(pageno, page) {
+                        let children = interior.cells.iter().map(|cell| cell.left_child_page_no);
+                        for child in children.chain(Some(interior.header.rightmost_pointer)) {
+                            parents.entry(child).or_default().push(pageno);
+                        }
+                    }
+                }
+                PageKind::LeafTable => leaves.push(pageno),
+                _ => {}
+            }
+            Ok(())
+        })?;
+
+        let mut cached_types = HashMap::default();
+        let found_objects;
+        let (objects, schema_pages) = match self.catalog() {
+            Ok(objects) => (objects, HashSet::default()),
+            Err(_) => {
+                let (objects, pages) = self.schema_from_leaves(&leaves, &mut cached_types)?;
+                found_objects = objects;
+                (found_objects.as_slice(), pages)
+            }
+        };
+
+        let schema_table = SqlSchema::try_from(SQLITE_SCHEMA_SQL.to_owned())?;
+        let mut roots = HashMap::default();
+        roots.insert(1, ("sqlite_schema", Some(&schema_table)));
+        for object in objects
+            .iter()
+            .filter(|object| object.kind == ObjectKind::Table && object.root_page != 0)
+        {
+            roots.insert(
+                object.root_page,
+                (object.name.as_str(), object.table_schema()),
+            );
+        }
+
+        for pageno in leaves {
+            let root_page = tree_root(pageno, &parents, &roots);
+            let (table, schema) = if schema_pages.contains(&pageno) {
+                (Some("sqlite_schema"), Some(&schema_table))
+            } else {
+                match roots.get(&root_page) {
+                    Some(&(table, schema)) => (Some(table), schema),
+                    None => (None, None),
+                }
+            };
+
+            let page_bytes = self.page_bytes(pageno)?;
+            let page_start_offset = if pageno == 1 { HEADER_SIZE } else { 0 };
+            let mut shapes: HashMap<usize, usize> = HashMap::default();
+            leaf_table_column_counts(
+                &page_bytes[page_start_offset..],
+                page_start_offset,
+                &self.header,
+                |columns| *shapes.entry(columns).or_default() += 1,
+            );
+            let Some((shape, _)) = shapes
+                .into_iter()
+                .max_by_key(|&(columns, count)| (count, columns))
+            else {
+                continue;
+            };
+
+            self.carve_table_leaf(pageno, &page_bytes, &mut cached_types, |cell, values| {
+                let row = SalvagedRow {
+                    table,
+                    root_page,
+                    pageno,
+                    shape,
+                    rowid: cell.rowid as i64,
+                };
+                match schema {
+                    Some(schema) => {
+                        let mut values = values.clone();
+                        schema.complete_row(row.rowid, &mut values);
+                        callback(&row, &values)
+                    }
+                    None => callback(&row, values),
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// hand every page of the database to `f`, up to the first one past its end
+    fn scan_pages<F>(&self, mut f: F) -> error::Result<()>
+    where
+        F: FnMut(u32, &[u8]) -> error::Result<()>,
+    {
+        for pageno in 1..=u32::MAX {
+            match self.page_bytes(pageno) {
+                Ok(page) => f(pageno, &page)?,
+                Err(SQLiteError::Corrupt {
+                    kind: CorruptionKind::PageOutOfRange,
+                    ..
+                }) => break,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// schema objects from the leaf pages all of whose records are sqlite_schema rows, and
+    /// those pages
+    fn schema_from_leaves(
+        &self,
+        leaves: &[u32],
+        cached_types: &mut HashMap<u64, Arc<Vec<SerialType>>>,
+    ) -> error::Result<(Vec<SchemaObject>, HashSet<u32>)> {
+        let mut objects = Vec::new();
+        let mut pages = HashSet::default();
+
+        for &pageno in leaves {
+            let page_bytes = self.page_bytes(pageno)?;
+            let mut page_objects = Vec::new();
+            let mut schema_rows_only = true;
+            self.carve_table_leaf(pageno, &page_bytes, cached_types, |_, values| {
+                match self.decode_schema_row(values) {
+                    Some(object) => page_objects.push(object),
+                    None => schema_rows_only = false,
+                }
+                Ok(())
+            })?;
+
+            if schema_rows_only && !page_objects.is_empty() {
+                objects.extend(page_objects);
+                pages.insert(pageno);
+            }
+        }
+
+        Ok((objects, pages))
+    }
+}
+
+fn page_kind(pageno: u32, page: &[u8]) -> PageKind {
+    let header_offset = if pageno == 1 { HEADER_SIZE } else { 0 };
+    match page.get(header_offset) {
+        Some(&PAGE_TYPE_INTERIOR_INDEX) => PageKind::InteriorIndex,
+        Some(&PAGE_TYPE_INTERIOR_TABLE) => PageKind::InteriorTable,
+        Some(&PAGE_TYPE_LEAF_INDEX) => PageKind::LeafIndex,
+        Some(&PAGE_TYPE_LEAF_TABLE) => PageKind::LeafTable,
+        _ => PageKind::Other,
+    }
+}
+
+/// the known root page `pageno` is reachable from, or else the top of its first chain of
+/// parents
+fn tree_root<T>(pageno: u32, parents: &HashMap<u32, Vec<u32>>, roots: &HashMap<u32, T>) -> u32 {
+    let mut top = None;
+    let mut visited = HashSet::default();
+    let mut stack = vec![(pageno, 0)];
+
+    while let Some((page, depth)) = stack.pop() {
+        if roots.contains_key(&page) {
+            return page;
+        }
+        match parents.get(&page) {
+            Some(up) if depth < MAX_TREE_DEPTH => stack.extend(
+                up.iter()
+                    .filter(|&&parent| visited.insert(parent))
+                    .map(|&parent| (parent, depth + 1)),
+            ),
+            _ => {
+                top.get_or_insert(page);
+            }
+        }
+    }
+
+    top.unwrap_or(pageno)
+}
+
+/// the page size at which the most pages after the first look like b-tree pages, the
+/// smallest of those that come close as every other page of a larger size looks fine too
+fn guess_page_size(pager: &impl Pager) -> error::Result<usize> {
+    let mut scores = Vec::new();
+    for page_size in (9..=16).map(|shift| 1 << shift) {
+        let (mut sampled, mut plausible) = (0, 0);
+        for pageno in 2..=SAMPLE_PAGES {
+            let Some(page) = pager.page(pageno, page_size)? else {
+                break;
+            };
+            sampled += 1;
+            plausible += plausible_btree_page(&page) as usize;
+        }
+        if plausible > 0 {
+            scores.push((page_size, plausible as f64 / sampled as f64));
+        }
+    }
+
+    let best = scores.iter().map(|&(_, score)| score).fold(0.0, f64::max);
+    Ok(scores
+        .into_iter()
+        .find(|&(_, score)| score >= best * 0.9)
+        .map_or(DEFAULT_PAGE_SIZE, |(page_size, _)| page_size))
+}
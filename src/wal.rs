@@ -0,0 +1,228 @@
+use memmap2::MmapOptions;
+use std::fs::File;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use winnow::error::ContextError;
+
+use crate::error;
+use crate::model::WalHeader;
+use crate::parser::{wal_frame_header, wal_header, WAL_FRAME_HEADER_SIZE, WAL_HEADER_SIZE};
+use crate::HashMap;
+
+/// a single valid frame of the WAL, `offset` points at the page image
+pub(crate) struct WalFrame {
+    pub(crate) pageno: u32,
+    pub(crate) db_size: u32,
+    pub(crate) offset: usize,
+}
+
+/// a transaction committed to the WAL
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalCommit {
+    /// 1-based in the order of the WAL, 0 stands for the database file before the first
+    pub number: usize,
+    /// frames written by the commit, as indexes into the committed frames
+    pub frames: Range<usize>,
+    /// size of the database in pages after the commit
+    pub db_size: u32,
+    /// pages written by the commit, in the order of their frames
+    pub pages: Vec<u32>,
+}
+
+/// write-ahead log of a database in WAL mode
+///
+/// only frames that belong to a committed transaction and pass the salt and checksum
+/// checks are kept, for every page the newest of them up to the commit the WAL is read at
+/// wins, which is the last one unless [`Wal::read_at`] says otherwise
+pub struct Wal {
+    buf: Arc<dyn AsRef<[u8]> + Send + Sync>,
+    pub header: WalHeader,
+    frames: Vec<WalFrame>,
+    commits: Vec<WalCommit>,
+    /// number of the commit the WAL is read at
+    commit: usize,
+    page_index: HashMap<u32, usize>,
+}
+
+/// `<db>-wal` next to the database file
+pub(crate) fn wal_path(database: &Path) -> PathBuf {
+    let mut path = database.as_os_str().to_owned();
+    path.push("-wal");
+    PathBuf::from(path)
+}
+
+#[inline(always)]
+fn checksum(big_endian: bool, data: &[u8], (mut s0, mut s1): (u32, u32)) -> (u32, u32) {
+    for chunk in data.chunks_exact(8) {
+        let (x0, x1) = if big_endian {
+            (
+                u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+                u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+            )
+        } else {
+            (
+                u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+                u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+            )
+        };
+        s0 = s0.wrapping_add(x0).wrapping_add(s1);
+        s1 = s1.wrapping_add(x1).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+impl Wal {
+    /// map the WAL file at `path`, returns `None` if it does not exist or holds no valid header
+    pub fn open<P: AsRef<Path>>(path: P) -> error::Result<Option<Wal>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        if (file.metadata()?.len() as usize) < WAL_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let mmap = unsafe { MmapOptions::new().map(&file) }?;
+        Ok(Wal::from_source(mmap))
+    }
+
+    /// read the WAL file at `path` into memory, like [`Wal::open`] without a memory map
+    pub fn read<P: AsRef<Path>>(path: P) -> error::Result<Option<Wal>> {
+        let buf = match std::fs::read(path) {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Wal::from_source(buf))
+    }
+
+    /// parse a WAL from any byte source, an invalid header means an empty WAL like in SQLite
+    pub fn from_source<S: AsRef<[u8]> + Send + Sync + 'static>(buf: S) -> Option<Wal> {
+        let bytes = buf.as_ref();
+        let mut input = bytes;
+        let header = wal_header::<ContextError>(&mut input).ok()?;
+        if !header.page_size.is_power_of_two() || !(512..=65536).contains(&header.page_size) {
+            return None;
+        }
+
+        let big_endian = header.big_endian_checksum();
+        let mut sums = checksum(big_endian, &bytes[..WAL_HEADER_SIZE - 8], (0, 0));
+        if sums != (header.checksum1, header.checksum2) {
+            return None;
+        }
+
+        let page_size = header.page_size as usize;
+        let frame_size = WAL_FRAME_HEADER_SIZE + page_size;
+
+        let mut frames = Vec::new();
+        let mut committed = 0;
+        let mut offset = WAL_HEADER_SIZE;
+
+        while offset + frame_size <= bytes.len() {
+            let frame_bytes = &bytes[offset..offset + frame_size];
+            let mut input = frame_bytes;
+            let Ok(frame) = wal_frame_header::<ContextError>(&mut input) else {
+                break;
+            };
+
+            if frame.salt1 != header.salt1 || frame.salt2 != header.salt2 {
+                break;
+            }
+
+            sums = checksum(big_endian, &frame_bytes[..8], sums);
+            sums = checksum(big_endian, &frame_bytes[WAL_FRAME_HEADER_SIZE..], sums);
+            if sums != (frame.checksum1, frame.checksum2) {
+                break;
+            }
+
+            frames.push(WalFrame {
+                pageno: frame.pageno,
+                db_size: frame.db_size,
+                offset: offset + WAL_FRAME_HEADER_SIZE,
+            });
+            if frame.db_size != 0 {
+                committed = frames.len();
+            }
+
+            offset += frame_size;
+        }
+
+        // frames after the last commit frame belong to a transaction that never finished
+        frames.truncate(committed);
+
+        let mut commits = Vec::new();
+        let mut first_frame = 0;
+        for (idx, frame) in frames.iter().enumerate() {
+            if frame.db_size != 0 {
+                commits.push(WalCommit {
+                    number: commits.len() + 1,
+                    frames: first_frame..idx + 1,
+                    db_size: frame.db_size,
+                    pages: frames[first_frame..=idx]
+                        .iter()
+                        .map(|frame| frame.pageno)
+                        .collect(),
+                });
+                first_frame = idx + 1;
+            }
+        }
+
+        let mut wal = Wal {
+            buf: Arc::new(buf),
+            header,
+            frames,
+            commit: commits.len(),
+            commits,
+            page_index: HashMap::default(),
+        };
+        wal.read_at(wal.commit);
+        Some(wal)
+    }
+
+    /// number of committed frames, up to the commit the WAL is read at
+    pub fn frame_count(&self) -> usize {
+        self.commit
+            .checked_sub(1)
+            .map_or(0, |idx| self.commits[idx].frames.end)
+    }
+
+    /// size of the database in pages after the commit the WAL is read at, `None` before
+    /// the first
+    pub fn db_size(&self) -> Option<u32> {
+        self.commit
+            .checked_sub(1)
+            .map(|idx| self.commits[idx].db_size)
+    }
+
+    /// every commit in the WAL, oldest first
+    pub fn commits(&self) -> &[WalCommit] {
+        &self.commits
+    }
+
+    /// number of the commit the WAL is read at, 0 when no frame is read
+    pub fn commit(&self) -> usize {
+        self.commit
+    }
+
+    /// read the WAL as it was right after commit `commit`, with 0 no frame is read and the
+    /// pages come from the database file, numbers past the last commit stop at the last
+    pub fn read_at(&mut self, commit: usize) {
+        self.commit = commit.min(self.commits.len());
+        self.page_index.clear();
+        for (idx, frame) in self.frames[..self.frame_count()].iter().enumerate() {
+            self.page_index.insert(frame.pageno, idx);
+        }
+    }
+
+    /// newest committed image of `pageno`, if the WAL has one
+    #[inline(always)]
+    pub(crate) fn page(&self, pageno: u32) -> Option<&[u8]> {
+        let frame = &self.frames[*self.page_index.get(&pageno)?];
+        let bytes = (*self.buf).as_ref();
+        Some(&bytes[frame.offset..frame.offset + self.header.page_size as usize])
+    }
+}
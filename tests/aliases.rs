@@ -0,0 +1,90 @@
+mod common;
+
+use common::{cell_offset, page_mut, read_fixture, text};
+use sqlite3_dump::model::Payload;
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// root page of column_alias, its only page
+const COLUMN_ALIAS_ROOT: u32 = 2;
+
+/// rowid, id and v of every row of `table`
+fn rows(database: Vec<u8>, table: &str) -> Vec<(i64, Option<i64>, Option<String>)> {
+    let reader = Reader::from_source(database).unwrap();
+    let mut rows = Vec::new();
+    reader
+        .stream_table_rows_sequential(table, |cell, values| {
+            let id = match values[0] {
+                Some(Payload::I64(id)) => Some(id),
+                None => None,
+                ref other => panic!("expected an integer, got {other:?}"),
+            };
+            rows.push((cell.rowid as i64, id, text(&values[1])));
+            Ok(())
+        })
+        .unwrap();
+    rows
+}
+
+#[test]
+fn alias_columns() {
+    let reader = Reader::from_source(read_fixture("aliases.db")).unwrap();
+    let aliases: Vec<_> = reader
+        .catalog()
+        .unwrap()
+        .iter()
+        .map(|object| (object.name.as_str(), object.rowid_alias()))
+        .collect();
+    assert_eq!(
+        aliases,
+        [
+            ("column_alias", Some(0)),
+            ("constraint_alias", Some(0)),
+            ("without_alias", None),
+            ("sqlite_autoindex_without_alias_1", None),
+            ("int_key", None),
+            ("sqlite_autoindex_int_key_1", None),
+            ("composite", None),
+            ("sqlite_autoindex_composite_1", None),
+        ]
+    );
+}
+
+#[test]
+fn rowid_in_the_alias_column() {
+    let database = read_fixture("aliases.db");
+    let expected = [
+        (10, Some(10), Some("ten".to_owned())),
+        (20, Some(20), Some("twenty".to_owned())),
+    ];
+    assert_eq!(rows(database.clone(), "column_alias"), expected);
+    assert_eq!(rows(database, "constraint_alias"), expected);
+}
+
+#[test]
+fn id_stored_in_the_record() {
+    let database = read_fixture("aliases.db");
+    let expected = [
+        (1, Some(10), Some("ten".to_owned())),
+        (2, Some(20), Some("twenty".to_owned())),
+    ];
+    for table in ["without_alias", "int_key", "composite"] {
+        assert_eq!(rows(database.clone(), table), expected, "{table}");
+    }
+}
+
+#[test]
+fn rowid_wins_over_a_stored_alias_value() {
+    // serial type 9 is the constant 1, SQLite always stores NULL in the alias column
+    let mut database = read_fixture("aliases.db");
+    let page = page_mut(&mut database, PAGE_SIZE, COLUMN_ALIAS_ROOT);
+    // payload size, rowid, header size, then the serial type of id
+    let cell = cell_offset(page, 0);
+    assert_eq!(page[cell + 3], 0);
+    page[cell + 3] = 9;
+
+    assert_eq!(
+        rows(database, "column_alias")[0],
+        (10, Some(10), Some("ten".to_owned()))
+    );
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use common::{fixture, page_mut, read_fixture, text};
+use sqlite3_dump::catalog::{Definition, ObjectKind};
+use sqlite3_dump::error::SQLiteError;
+use sqlite3_dump::Reader;
+
+#[test]
+fn lists_every_schema_object() {
+    let reader = Reader::open_mmap(fixture("catalog.db")).unwrap();
+    let objects: Vec<_> = reader
+        .catalog()
+        .unwrap()
+        .iter()
+        .map(|object| {
+            (
+                object.kind,
+                object.name.as_str(),
+                object.table_name.as_str(),
+                object.root_page,
+            )
+        })
+        .collect();
+    assert_eq!(
+        objects[..6],
+        [
+            (ObjectKind::Table, "Customers", "Customers", 2),
+            (
+                ObjectKind::Index,
+                "sqlite_autoindex_Customers_1",
+                "Customers",
+                3
+            ),
+            (ObjectKind::Index, "customers_name", "Customers", 4),
+            (ObjectKind::View, "customer_names", "customer_names", 0),
+            (ObjectKind::Trigger, "customers_audit", "Customers", 0),
+            (ObjectKind::VirtualTable, "search", "search", 0),
+        ]
+    );
+    // the tables the fts5 module keeps its index in
+    assert!(objects[6..]
+        .iter()
+        .all(|&(kind, name, _, root_page)| kind == ObjectKind::Table
+            && name.starts_with("search_")
+            && root_page > 0));
+
+    let catalog = reader.catalog().unwrap();
+    assert!(catalog[1].sql.is_none() && catalog[1].definition.is_none());
+    assert!(matches!(catalog[2].definition, Some(Definition::Index(_))));
+    assert!(matches!(catalog[3].definition, Some(Definition::View(_))));
+    assert!(matches!(
+        catalog[4].definition,
+        Some(Definition::Trigger(_))
+    ));
+    assert_eq!(
+        catalog[0].column_names(),
+        ["id", "name", "email"].map(String::from)
+    );
+    assert_eq!(catalog[0].rowid_alias(), Some(0));
+}
+
+#[test]
+fn names_are_case_insensitive() {
+    let reader = Reader::open_mmap(fixture("catalog.db")).unwrap();
+    let object = reader.schema_object("CUSTOMERS").unwrap().unwrap();
+    assert_eq!(object.name, "Customers");
+
+    let mut names = Vec::new();
+    reader
+        .stream_table_rows_sequential("customers", |_, values| {
+            names.push(text(&values[1]).unwrap());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(names.len(), 10);
+    assert_eq!(names[0], "customer 0");
+
+    let mut entries = 0;
+    reader
+        .stream_index_entries("CUSTOMERS_NAME", |_| {
+            entries += 1;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(entries, 10);
+}
+
+#[test]
+fn views_have_no_rows() {
+    let reader = Reader::open_mmap(fixture("catalog.db")).unwrap();
+    let result = reader.stream_table_rows_sequential("customer_names", |_, _| Ok(()));
+    assert!(matches!(result, Err(SQLiteError::Other(_))), "{result:?}");
+}
+
+#[test]
+fn damaged_schema_page() {
+    let mut database = read_fixture("catalog.db");
+    page_mut(&mut database, 512, 1)[100] = 0xff;
+
+    let reader = Reader::from_source(database).unwrap();
+    assert!(matches!(
+        reader.catalog(),
+        Err(SQLiteError::Corrupt { page: 1, .. })
+    ));
+}
+
+#[test]
+fn tables_map_leaves_out_virtual_tables() {
+    let reader = Reader::open_mmap(fixture("catalog.db")).unwrap();
+    let tables = reader.get_tables_map().unwrap();
+    assert!(tables.contains_key("Customers"));
+    assert!(tables.contains_key("search_content"));
+    assert!(!tables.contains_key("search"));
+    assert!(!tables.contains_key("customer_names"));
+
+    let search = reader.schema_object("search").unwrap().unwrap();
+    assert_eq!(search.kind, ObjectKind::VirtualTable);
+}
@@ -0,0 +1,36 @@
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use sqlite3_dump::model::{Payload, TextEncoding};
+
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+pub fn read_fixture(name: &str) -> Vec<u8> {
+    std::fs::read(fixture(name)).unwrap()
+}
+
+/// bytes of page `pageno` of a database with `page_size` byte pages
+pub fn page_mut(database: &mut [u8], page_size: usize, pageno: u32) -> &mut [u8] {
+    let start = page_size * (pageno as usize - 1);
+    &mut database[start..start + page_size]
+}
+
+/// offset of the `cell`th cell of a b-tree page that isn't page 1
+pub fn cell_offset(page: &[u8], cell: usize) -> usize {
+    let header_size = if page[0] & 0x08 != 0 { 8 } else { 12 };
+    let pointer = header_size + 2 * cell;
+    u16::from_be_bytes([page[pointer], page[pointer + 1]]) as usize
+}
+
+/// a text column of a UTF-8 database as a string, `None` for NULL
+pub fn text(value: &Option<Payload<'_>>) -> Option<String> {
+    value.as_ref().map(|value| match value {
+        Payload::Text(text) => text.decode(TextEncoding::Utf8).into_owned(),
+        other => panic!("expected text, got {other:?}"),
+    })
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use common::{read_fixture, text};
+use sqlite3_dump::compression::Compression;
+use sqlite3_dump::{Pager, Reader};
+
+const COMPRESSIONS: [Compression; 3] = [Compression::Gzip, Compression::Zstd, Compression::Xz];
+
+fn compress(compression: Compression, data: &[u8]) -> Vec<u8> {
+    match compression {
+        Compression::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        }
+        Compression::Zstd => zstd::encode_all(data, 0).unwrap(),
+        Compression::Xz => {
+            let mut out = Vec::new();
+            lzma_rs::xz_compress(&mut &data[..], &mut out).unwrap();
+            out
+        }
+    }
+}
+
+/// `data` written to a file of its own under the target directory
+fn temp_file(name: &str, data: &[u8]) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn row_count(reader: &Reader<impl Pager>) -> usize {
+    let mut rows = 0;
+    reader
+        .stream_table_rows_sequential("people", |_, values| {
+            assert!(text(&values[1]).is_some());
+            rows += 1;
+            Ok(())
+        })
+        .unwrap();
+    rows
+}
+
+#[test]
+fn detects_magic_bytes() {
+    let database = read_fixture("people.db");
+    for compression in COMPRESSIONS {
+        let compressed = compress(compression, &database);
+        assert_eq!(Compression::detect(&compressed), Some(compression));
+    }
+    assert_eq!(Compression::detect(&database), None);
+}
+
+#[test]
+fn reads_every_compression() {
+    let database = read_fixture("people.db");
+    for compression in COMPRESSIONS {
+        let compressed = compress(compression, &database);
+        let path = temp_file(&format!("people.db.{compression:?}"), &compressed);
+
+        assert_eq!(row_count(&Reader::read_from(&compressed[..]).unwrap()), 200);
+        assert_eq!(row_count(&Reader::open_mmap(&path).unwrap()), 200);
+        assert_eq!(row_count(&Reader::open_pread(&path, 4).unwrap()), 200);
+    }
+}
+
+#[test]
+fn concatenated_gzip_members() {
+    let database = read_fixture("people.db");
+    let (head, tail) = database.split_at(3000);
+    let mut compressed = compress(Compression::Gzip, head);
+    compressed.extend(compress(Compression::Gzip, tail));
+    assert_eq!(row_count(&Reader::read_from(&compressed[..]).unwrap()), 200);
+}
+
+#[test]
+fn truncated_stream() {
+    let database = read_fixture("people.db");
+    for compression in COMPRESSIONS {
+        let compressed = compress(compression, &database);
+        let truncated = &compressed[..compressed.len() / 2];
+        assert!(
+            Reader::read_from(truncated).is_err(),
+            "{compression:?} read from half its stream"
+        );
+        let path = temp_file(&format!("truncated.db.{compression:?}"), truncated);
+        assert!(Reader::open_mmap(&path).is_err(), "{compression:?}");
+    }
+}
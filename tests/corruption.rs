@@ -0,0 +1,115 @@
+mod common;
+
+use common::{cell_offset, page_mut, read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// root page of the people table, an interior page over its leaves
+const PEOPLE_ROOT: u32 = 2;
+
+/// rowid and name of every person
+fn people_rows(database: Vec<u8>) -> Result<Vec<(i64, Option<String>)>, SQLiteError> {
+    let reader = Reader::from_source(database)?;
+    let mut rows = Vec::new();
+    reader.stream_table_rows_sequential("people", |cell, values| {
+        rows.push((cell.rowid as i64, text(&values[1])));
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+fn corruption(result: Result<impl std::fmt::Debug, SQLiteError>) -> (u32, CorruptionKind) {
+    match result {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => (page, kind),
+        other => panic!("expected a corruption error, got {other:?}"),
+    }
+}
+
+/// the leaf page under the first cell of the root page
+fn first_leaf(database: &mut [u8]) -> u32 {
+    let root = page_mut(database, PAGE_SIZE, PEOPLE_ROOT);
+    let cell = cell_offset(root, 0);
+    u32::from_be_bytes(root[cell..cell + 4].try_into().unwrap())
+}
+
+#[test]
+fn reads_every_row() {
+    let rows = people_rows(read_fixture("people.db")).unwrap();
+    assert_eq!(rows.len(), 200);
+    for (i, (rowid, name)) in rows.into_iter().enumerate() {
+        assert_eq!(rowid, i as i64 + 1);
+        assert_eq!(name, Some(format!("person {i}")));
+    }
+}
+
+#[test]
+fn child_pointer_to_page_zero() {
+    let mut database = read_fixture("people.db");
+    let root = page_mut(&mut database, PAGE_SIZE, PEOPLE_ROOT);
+    root[8..12].copy_from_slice(&0u32.to_be_bytes());
+
+    let (page, kind) = corruption(people_rows(database));
+    assert_eq!(page, 0);
+    assert_eq!(kind, CorruptionKind::PageOutOfRange);
+}
+
+#[test]
+fn column_past_the_end_of_the_record() {
+    let mut database = read_fixture("people.db");
+    let leaf = first_leaf(&mut database);
+    let page = page_mut(&mut database, PAGE_SIZE, leaf);
+    // payload size, rowid, header size, id, then the serial type of the name
+    let cell = cell_offset(page, 0);
+    page[cell + 4] = 127;
+
+    let (page, kind) = corruption(people_rows(database));
+    assert_eq!(page, leaf);
+    assert_eq!(kind, CorruptionKind::Malformed);
+}
+
+#[test]
+fn record_header_without_columns() {
+    let mut database = read_fixture("people.db");
+    let leaf = first_leaf(&mut database);
+    let page = page_mut(&mut database, PAGE_SIZE, leaf);
+    let cell = cell_offset(page, 0);
+    page[cell + 2] = 1;
+
+    let (page, kind) = corruption(people_rows(database));
+    assert_eq!(page, leaf);
+    assert_eq!(kind, CorruptionKind::InvalidHeaderSize(1));
+}
+
+#[test]
+fn index_page_in_a_table_tree() {
+    let mut database = read_fixture("people.db");
+    let leaf = first_leaf(&mut database);
+    page_mut(&mut database, PAGE_SIZE, leaf)[0] = 0x0a;
+
+    let (page, kind) = corruption(people_rows(database));
+    assert_eq!(page, leaf);
+    assert_eq!(kind, CorruptionKind::InvalidPageType(0x0a));
+}
+
+#[test]
+fn truncated_file() {
+    let mut database = read_fixture("people.db");
+    database.truncate(4 * PAGE_SIZE);
+
+    let (_, kind) = corruption(people_rows(database));
+    assert_eq!(kind, CorruptionKind::PageOutOfRange);
+}
+
+#[test]
+fn cursor_stops_at_page_zero() {
+    let mut database = read_fixture("people.db");
+    let root = page_mut(&mut database, PAGE_SIZE, PEOPLE_ROOT);
+    root[8..12].copy_from_slice(&0u32.to_be_bytes());
+
+    let reader = Reader::from_source(database).unwrap();
+    let rows = reader.table_rows("people").unwrap();
+    let (page, kind) = corruption(rows.collect::<Result<Vec<_>, _>>().map(|rows| rows.len()));
+    assert_eq!(page, 0);
+    assert_eq!(kind, CorruptionKind::PageOutOfRange);
+}
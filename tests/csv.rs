@@ -0,0 +1,99 @@
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use common::{fixture, read_fixture};
+
+fn csv(args: &[&str], stdin: Option<Vec<u8>>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_csv"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut pipe = child.stdin.take().unwrap();
+    if let Some(stdin) = stdin {
+        // the pipe breaks when the options are rejected before stdin is read
+        let _ = pipe.write_all(&stdin);
+    }
+    drop(pipe);
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn table_as_of_a_commit() {
+    let history = fixture("history.db");
+    let output = csv(
+        &[history.to_str().unwrap(), "-t", "t", "--commit", "2"],
+        None,
+    );
+    assert_eq!(stdout(&output), "1,one\n2,TWO\n3,three\n4,four\n5,five\n");
+
+    let output = csv(
+        &[history.to_str().unwrap(), "-t", "t", "--diff-from", "1"],
+        None,
+    );
+    assert_eq!(
+        stdout(&output),
+        "updated,2,2,TWO\ndeleted,3,3,three\ninserted,6,6,six\n"
+    );
+}
+
+#[test]
+fn commit_options_on_stdin() {
+    for args in [
+        &["-", "--commits"][..],
+        &["-", "-t", "t", "--commit", "1"],
+        &["-", "-t", "t", "--diff-from", "1"],
+    ] {
+        let output = csv(args, Some(read_fixture("history.db")));
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("stdin"), "{stderr}");
+    }
+}
+
+#[test]
+fn table_from_stdin() {
+    let output = csv(&["-", "-t", "people"], Some(read_fixture("people.db")));
+    let rows = stdout(&output);
+    assert_eq!(rows.lines().count(), 200);
+    assert_eq!(rows.lines().next(), Some("1,person 0,20"));
+}
+
+#[test]
+fn salvage_with_pread_or_stdin() {
+    let mut database = read_fixture("people.db");
+    database[..100].fill(0);
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("salvage_with_pread.db");
+    std::fs::write(&path, &database).unwrap();
+    let path = path.to_str().unwrap();
+
+    let mmap = stdout(&csv(&[path, "--salvage"], None));
+    assert_eq!(
+        mmap.lines()
+            .filter(|line| line.starts_with("people,"))
+            .count(),
+        200
+    );
+    assert_eq!(stdout(&csv(&[path, "--salvage", "--pread"], None)), mmap);
+    assert_eq!(stdout(&csv(&["-", "--salvage"], Some(database))), mmap);
+}
+
+#[test]
+fn table_names_ignore_case() {
+    let people = fixture("people.db");
+    let output = csv(&[people.to_str().unwrap(), "-t", "PEOPLE"], None);
+    assert_eq!(stdout(&output).lines().count(), 200);
+}
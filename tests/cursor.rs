@@ -0,0 +1,116 @@
+mod common;
+
+use common::{cell_offset, page_mut, read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// root page of the people table, an interior page over its leaves
+const PEOPLE_ROOT: u32 = 2;
+
+/// rowid and name of a person
+type Person = (i64, Option<String>);
+
+fn people(rowids: std::ops::RangeInclusive<i64>) -> Vec<Person> {
+    rowids
+        .map(|rowid| (rowid, Some(format!("person {}", rowid - 1))))
+        .collect()
+}
+
+fn person(row: sqlite3_dump::TableRow<'_>) -> Person {
+    (row.rowid, text(&row.values().unwrap()[1]))
+}
+
+/// people.db and the leaf under the first cell of the root page
+fn first_leaf() -> (Vec<u8>, u32) {
+    let mut database = read_fixture("people.db");
+    let root = page_mut(&mut database, PAGE_SIZE, PEOPLE_ROOT);
+    let cell = cell_offset(root, 0);
+    let leaf = u32::from_be_bytes(root[cell..cell + 4].try_into().unwrap());
+    (database, leaf)
+}
+
+#[test]
+fn every_row() {
+    let reader = Reader::from_source(read_fixture("people.db")).unwrap();
+    let rows: Vec<_> = reader
+        .table_rows("people")
+        .unwrap()
+        .map(|row| person(row.unwrap()))
+        .collect();
+    assert_eq!(rows, people(1..=200));
+}
+
+#[test]
+fn rowid_range() {
+    let reader = Reader::from_source(read_fixture("people.db")).unwrap();
+    let rows: Vec<_> = reader
+        .table_rows_range("people", 98..=103)
+        .unwrap()
+        .map(|row| person(row.unwrap()))
+        .collect();
+    assert_eq!(rows, people(98..=103));
+}
+
+#[test]
+fn cursors_in_step() {
+    let reader = Reader::from_source(read_fixture("people.db")).unwrap();
+    let low = reader.table_rows_range("people", ..=100).unwrap();
+    let high = reader.table_rows_range("people", 101..).unwrap();
+    let pairs: Vec<_> = low
+        .zip(high)
+        .take(3)
+        .map(|(low, high)| (low.unwrap().rowid, high.unwrap().rowid))
+        .collect();
+    assert_eq!(pairs, [(1, 101), (2, 102), (3, 103)]);
+}
+
+#[test]
+fn spilled_record() {
+    let reader = Reader::from_source(read_fixture("overflow.db")).unwrap();
+    let row = reader.table_rows("docs").unwrap().nth(1).unwrap().unwrap();
+    let body: String = (0..400).map(|i| format!("line {i}\n")).collect();
+    assert_eq!(text(&row.values().unwrap()[2]), Some(body));
+}
+
+#[test]
+fn record_decoded_on_demand() {
+    let (mut database, leaf) = first_leaf();
+    let page = page_mut(&mut database, PAGE_SIZE, leaf);
+    // payload size, rowid, header size, id, then the serial type of the name
+    let cell = cell_offset(page, 0);
+    page[cell + 4] = 127;
+
+    let reader = Reader::from_source(database).unwrap();
+    let mut rows = reader.table_rows("people").unwrap();
+    let damaged = rows.next().unwrap().unwrap();
+    assert_eq!(damaged.rowid, 1);
+    match damaged.values() {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => {
+            assert_eq!(page, leaf);
+            assert_eq!(kind, CorruptionKind::Malformed);
+        }
+        other => panic!("expected a malformed record, got {other:?}"),
+    }
+    assert_eq!(person(rows.next().unwrap().unwrap()), people(2..=2)[0]);
+}
+
+#[test]
+fn index_page_in_a_table_tree() {
+    let (mut database, leaf) = first_leaf();
+    page_mut(&mut database, PAGE_SIZE, leaf)[0] = 0x0a;
+
+    let reader = Reader::from_source(database).unwrap();
+    let mut rows = reader.table_rows("people").unwrap();
+    match rows.next() {
+        Some(Err(SQLiteError::Corrupt { page, kind, .. })) => {
+            assert_eq!(page, leaf);
+            assert_eq!(kind, CorruptionKind::InvalidPageType(0x0a));
+        }
+        other => panic!(
+            "expected an invalid page type, got {:?}",
+            other.map(|row| row.map(|row| row.rowid))
+        ),
+    }
+    assert!(rows.next().is_none());
+}
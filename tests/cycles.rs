@@ -0,0 +1,104 @@
+mod common;
+
+use common::{cell_offset, page_mut, read_fixture};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// root page of the people table, an interior page over its leaves
+const PEOPLE_ROOT: u32 = 2;
+/// levels of b-tree the reader follows before giving up
+const MAX_TREE_DEPTH: usize = 20;
+
+fn row_count(database: Vec<u8>, table: &str) -> Result<usize, SQLiteError> {
+    let reader = Reader::from_source(database)?;
+    let mut rows = 0;
+    reader.stream_table_rows_sequential(table, |_, _| {
+        rows += 1;
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+fn corruption(result: Result<usize, SQLiteError>) -> (u32, CorruptionKind) {
+    match result {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => (page, kind),
+        other => panic!("expected a corruption error, got {other:?}"),
+    }
+}
+
+/// people.db with `extra` interior pages without cells between the root page and its
+/// rightmost leaf, one under the other
+fn people_with_chain(extra: u32) -> Vec<u8> {
+    let mut database = read_fixture("people.db");
+    let pages = (database.len() / PAGE_SIZE) as u32;
+    let root = page_mut(&mut database, PAGE_SIZE, PEOPLE_ROOT);
+    let mut rightmost = u32::from_be_bytes(root[8..12].try_into().unwrap());
+    root[8..12].copy_from_slice(&(pages + 1).to_be_bytes());
+
+    let mut chain = Vec::new();
+    for pageno in (pages + 1..=pages + extra).rev() {
+        let mut page = vec![0; PAGE_SIZE];
+        page[0] = 0x05;
+        page[5..7].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+        page[8..12].copy_from_slice(&rightmost.to_be_bytes());
+        rightmost = pageno;
+        chain.splice(..0, page);
+    }
+    database.extend(chain);
+    database[28..32].copy_from_slice(&(pages + extra).to_be_bytes());
+    database
+}
+
+#[test]
+fn child_pointer_to_its_parent() {
+    let mut database = read_fixture("people.db");
+    let root = page_mut(&mut database, PAGE_SIZE, PEOPLE_ROOT);
+    let cell = cell_offset(root, 0);
+    root[cell..cell + 4].copy_from_slice(&PEOPLE_ROOT.to_be_bytes());
+
+    assert_eq!(
+        corruption(row_count(database, "people")),
+        (PEOPLE_ROOT, CorruptionKind::PageCycle)
+    );
+}
+
+#[test]
+fn subtree_reached_twice() {
+    let mut database = read_fixture("people.db");
+    let root = page_mut(&mut database, PAGE_SIZE, PEOPLE_ROOT);
+    let rightmost: [u8; 4] = root[8..12].try_into().unwrap();
+    let cell = cell_offset(root, 0);
+    root[cell..cell + 4].copy_from_slice(&rightmost);
+
+    assert_eq!(
+        corruption(row_count(database, "people")),
+        (u32::from_be_bytes(rightmost), CorruptionKind::PageCycle)
+    );
+}
+
+#[test]
+fn overflow_chain_loops() {
+    // the body of doc 2 spills into pages 4 to 9
+    let mut database = read_fixture("overflow.db");
+    page_mut(&mut database, PAGE_SIZE, 6)[..4].copy_from_slice(&4u32.to_be_bytes());
+
+    assert_eq!(
+        corruption(row_count(database, "docs")),
+        (4, CorruptionKind::PageCycle)
+    );
+}
+
+#[test]
+fn deepest_tree() {
+    // the root page, the chain, then the leaf
+    let database = people_with_chain(MAX_TREE_DEPTH as u32 - 2);
+    assert_eq!(row_count(database, "people").unwrap(), 200);
+}
+
+#[test]
+fn tree_too_deep() {
+    let database = people_with_chain(MAX_TREE_DEPTH as u32 - 1);
+    let (_, kind) = corruption(row_count(database, "people"));
+    assert_eq!(kind, CorruptionKind::TreeTooDeep(MAX_TREE_DEPTH));
+}
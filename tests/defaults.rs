@@ -0,0 +1,96 @@
+mod common;
+
+use common::read_fixture;
+use sqlite3_dump::model::{Payload, TextEncoding};
+use sqlite3_dump::Reader;
+
+/// a value the way SQL would write it
+fn sql(value: &Option<Payload<'_>>) -> String {
+    match value {
+        None => "NULL".to_owned(),
+        Some(Payload::I64(n)) => n.to_string(),
+        Some(Payload::F64(n)) => n.to_string(),
+        Some(Payload::Text(text)) => format!("'{}'", text.decode(TextEncoding::Utf8)),
+        Some(Payload::Blob(blob)) => {
+            let hex: String = blob.iter().map(|byte| format!("{byte:02x}")).collect();
+            format!("x'{hex}'")
+        }
+    }
+}
+
+fn items(database: Vec<u8>) -> Vec<Vec<String>> {
+    let reader = Reader::from_source(database).unwrap();
+    let mut rows = Vec::new();
+    reader
+        .stream_table_rows_sequential("items", |_, values| {
+            rows.push(values.iter().map(sql).collect());
+            Ok(())
+        })
+        .unwrap();
+    rows
+}
+
+#[test]
+fn rows_written_before_the_columns_were_added() {
+    let old = |id: i64, name: &str| {
+        [
+            &id.to_string(),
+            name,
+            "7",
+            "-1.5",
+            "'it's'",
+            "x'cafe'",
+            "NULL",
+        ]
+        .map(str::to_owned)
+    };
+    assert_eq!(
+        items(read_fixture("altered.db")),
+        [
+            old(1, "'a'"),
+            old(2, "'b'"),
+            old(3, "'c'"),
+            ["4", "'d'", "1", "2.5", "'own'", "x'01'", "'n'"].map(str::to_owned),
+        ]
+    );
+}
+
+#[test]
+fn cursor_fills_in_defaults() {
+    let reader = Reader::from_source(read_fixture("altered.db")).unwrap();
+    let row = reader.table_rows("items").unwrap().next().unwrap().unwrap();
+    let values: Vec<_> = row.values().unwrap().iter().map(sql).collect();
+    assert_eq!(
+        values,
+        ["1", "'a'", "7", "-1.5", "'it's'", "x'cafe'", "NULL"]
+    );
+}
+
+#[test]
+fn without_rowid_rows() {
+    let reader = Reader::from_source(read_fixture("altered.db")).unwrap();
+    let mut rows = Vec::new();
+    reader
+        .stream_without_rowid_rows("pairs", |values| {
+            rows.push(values.iter().map(sql).collect::<Vec<_>>());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(rows, [["'one'", "1", "1"], ["'two'", "2", "0"]]);
+}
+
+#[test]
+fn unreadable_create_table() {
+    // without a schema the records are read as they are stored
+    let mut database = read_fixture("altered.db");
+    let sql = b"CREATE TABLE items(";
+    let at = database
+        .windows(sql.len())
+        .position(|window| window == sql)
+        .unwrap();
+    database[at..at + 6].copy_from_slice(b"CRATE ");
+
+    let rows = items(database);
+    assert_eq!(rows[0], ["NULL", "'a'"]);
+    assert_eq!(rows[3].len(), 7);
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::{cell_offset, fixture, page_mut, read_fixture};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::model::{Payload, TextEncoding};
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// root page of t, its only page
+const T_ROOT: u32 = 2;
+
+/// s and b of every row, `None` for NULL
+type Row = (Option<String>, Option<Vec<u8>>);
+
+fn rows(database: Vec<u8>) -> Result<Vec<Row>, SQLiteError> {
+    let reader = Reader::from_source(database)?;
+    let mut rows = Vec::new();
+    reader.stream_table_rows_sequential("t", |_, values| {
+        let s = match values[1] {
+            Some(Payload::Text(ref s)) => Some(s.decode(TextEncoding::Utf8).into_owned()),
+            None => None,
+            ref other => panic!("expected text, got {other:?}"),
+        };
+        let b = match values[2] {
+            Some(Payload::Blob(b)) => Some(b.to_vec()),
+            None => None,
+            ref other => panic!("expected a blob, got {other:?}"),
+        };
+        rows.push((s, b));
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+#[test]
+fn empty_values_are_not_null() {
+    assert_eq!(
+        rows(read_fixture("empty.db")).unwrap(),
+        [
+            (Some(String::new()), Some(Vec::new())),
+            (None, None),
+            (Some("a".to_owned()), Some(vec![0])),
+        ]
+    );
+}
+
+#[test]
+fn csv_quotes_empty_values() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_csv"))
+        .args([fixture("empty.db").to_str().unwrap(), "-t", "t"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "1,\"\",\"\"\n2,,\n3,a,00\n"
+    );
+}
+
+#[test]
+fn empty_blob_grown_past_the_record() {
+    // serial type 12 is an empty blob, 15 is one byte of text the record doesn't have
+    let mut database = read_fixture("empty.db");
+    let page = page_mut(&mut database, PAGE_SIZE, T_ROOT);
+    // payload size, rowid, header size, id, s, then the serial type of b
+    let cell = cell_offset(page, 0);
+    assert_eq!(page[cell + 5], 12);
+    page[cell + 5] = 15;
+
+    match rows(database) {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => {
+            assert_eq!(page, T_ROOT);
+            assert_eq!(kind, CorruptionKind::Malformed);
+        }
+        other => panic!("expected a malformed record, got {other:?}"),
+    }
+}
@@ -0,0 +1,327 @@
+#!/usr/bin/env python3
+"""Regenerate the fixture databases of the integration tests.
+
+python3 make_fixtures.py [fixture...], every fixture when none is named. A WAL gets a
+random salt, so only regenerate the fixtures you change.
+"""
+
+import os
+import shutil
+import sqlite3
+import sys
+
+HERE = os.path.dirname(os.path.abspath(__file__))
+
+
+def path(name):
+    return os.path.join(HERE, name)
+
+
+def fresh(name, page_size=512):
+    for suffix in ("", "-wal", "-journal", "-shm"):
+        if os.path.exists(path(name + suffix)):
+            os.remove(path(name + suffix))
+    conn = sqlite3.connect(path(name), isolation_level=None)
+    conn.execute(f"pragma page_size={page_size}")
+    return conn
+
+
+def empty():
+    conn = fresh("empty.db")
+    conn.execute("create table t(id integer primary key, s text, b blob)")
+    conn.executemany(
+        "insert into t(s, b) values (?, ?)", [("", b""), (None, None), ("a", b"\x00")]
+    )
+    conn.close()
+
+
+def freelist():
+    # the rows deleted from the start of the table free whole leaf pages
+    conn = fresh("freelist.db")
+    conn.execute("pragma secure_delete=0")
+    conn.execute("create table notes(id integer primary key, body text)")
+    conn.executemany(
+        "insert into notes(body) values (?)", [(f"note {i} " + "x" * 40,) for i in range(1, 301)]
+    )
+    conn.execute("delete from notes where id <= 150")
+    conn.close()
+
+
+def freeblocks():
+    # two deleted rows leave two freeblocks on the only leaf, no rowid alias so recovery
+    # reads the serial types left in them, the tests overwrite those
+    conn = fresh("freeblocks.db")
+    conn.execute("pragma secure_delete=0")
+    conn.execute("create table t(a text, b text, c text, d text)")
+    conn.executemany(
+        "insert into t values (?, ?, ?, ?)",
+        [(f"a{i}" * 4, f"b{i}" * 4, f"c{i}" * 4, f"d{i}" * 4) for i in range(10)],
+    )
+    conn.execute("delete from t where rowid in (3, 7)")
+    conn.close()
+
+
+def history():
+    # three commits in the -wal file: the rows, an update, then a delete and an insert
+    conn = fresh("history.db")
+    conn.execute("pragma journal_mode=wal")
+    conn.execute("pragma wal_autocheckpoint=0")
+    conn.executescript(
+        """
+        begin;
+        create table t(id integer primary key, name text);
+        insert into t(name) values ('one'), ('two'), ('three'), ('four'), ('five');
+        commit;
+        update t set name = 'TWO' where id = 2;
+        begin;
+        delete from t where id = 3;
+        insert into t(name) values ('six');
+        commit;
+        """
+    )
+    snapshot(conn, "history.db")
+
+
+def overflow():
+    # the body of doc 2 spills into the overflow chain of pages 4 to 9, the title and data
+    # of doc 3 spill too, in the table and in the index on title
+    conn = fresh("overflow.db")
+    conn.executescript(
+        """
+        create table docs(id integer primary key, title text, body text, data blob);
+        create index docs_title on docs(title);
+        """
+    )
+    conn.executemany(
+        "insert into docs(title, body, data) values (?, ?, ?)",
+        [
+            ("short", "hello", b"\x01"),
+            ("long body", "".join(f"line {i}\n" for i in range(400)), None),
+            ("t" * 1000, None, bytes(range(256)) * 8),
+        ],
+    )
+    conn.close()
+
+
+def hot_journal():
+    # the update spills into the database file before it commits, the -journal file keeps
+    # the pages as they were before it, a segment for every spill, and the inserts append
+    # pages past the size the journal records
+    conn = fresh("hot.db")
+    conn.execute("create table t(id integer primary key, name text)")
+    conn.executemany("insert into t(name) values (?)", [(f"before {i}",) for i in range(200)])
+    conn.execute("pragma cache_size=2")
+    conn.execute("begin")
+    conn.execute("update t set name = 'after ' || (id - 1)")
+    conn.executemany("insert into t(name) values (?)", [(f"new {i}",) for i in range(100)])
+    snapshot(conn, "hot.db", "-journal")
+
+
+def people():
+    conn = fresh("people.db")
+    conn.execute("create table people(id integer primary key, name text, age integer)")
+    conn.execute("create index people_name on people(name)")
+    conn.executemany(
+        "insert into people(name, age) values (?, ?)",
+        [(f"person {i}", 20 + i % 50) for i in range(200)],
+    )
+    conn.close()
+
+
+def aliases():
+    # only the first two tables keep id in the rowid, the others store it in the record
+    conn = fresh("aliases.db")
+    conn.executescript(
+        """
+        create table column_alias(id integer primary key, v text);
+        create table constraint_alias(id Integer, v text, primary key(id desc));
+        create table without_alias(id integer primary key desc, v text);
+        create table int_key(id int primary key, v text);
+        create table composite(id integer, v text, primary key(id, v));
+        """
+    )
+    for (table,) in conn.execute("select name from sqlite_schema where type = 'table'").fetchall():
+        conn.execute(f"insert into {table}(id, v) values (10, 'ten'), (20, 'twenty')")
+    conn.close()
+
+
+def altered():
+    # the first three items and the first pair are written before the columns are added,
+    # SQLite doesn't rewrite their records
+    conn = fresh("altered.db")
+    conn.executescript(
+        """
+        create table items(id integer primary key, name text);
+        insert into items(name) values ('a'), ('b'), ('c');
+        create table pairs(k text primary key, v) without rowid;
+        insert into pairs values ('one', 1);
+        alter table items add column qty integer default 7;
+        alter table items add column price real default -1.5;
+        alter table items add column label text default 'it''s';
+        alter table items add column data blob default x'cafe';
+        alter table items add column note;
+        alter table pairs add column flag integer default true;
+        insert into items(name, qty, price, label, data, note)
+            values ('d', 1, 2.5, 'own', x'01', 'n');
+        insert into pairs values ('two', 2, 0);
+        """
+    )
+    conn.close()
+
+
+def catalog():
+    conn = fresh("catalog.db")
+    conn.executescript(
+        """
+        create table Customers(id integer primary key, name text not null, email text unique);
+        create index customers_name on Customers(name);
+        create view customer_names as select name from Customers;
+        create trigger customers_audit after insert on Customers begin select 1; end;
+        create virtual table search using fts5(name);
+        """
+    )
+    conn.executemany(
+        "insert into Customers(name, email) values (?, ?)",
+        [(f"customer {i}", f"c{i}@example.com") for i in range(10)],
+    )
+    conn.close()
+
+
+def recover():
+    # every third person and a run of notes are deleted, the freed cells stay in place
+    conn = fresh("recover.db", page_size=1024)
+    conn.execute("pragma secure_delete=0")
+    conn.executescript(
+        """
+        create table people(id integer primary key, name text, age integer);
+        create table notes(id integer primary key, body text, created real);
+        """
+    )
+    conn.executemany(
+        "insert into people(name, age) values (?, ?)",
+        [(f"person {i}", 20 + i % 50) for i in range(1, 201)],
+    )
+    conn.executemany(
+        "insert into notes(body, created) values (?, ?)",
+        [(f"note number {i} " + "lorem ipsum " * (i % 5), 1.5 * i) for i in range(1, 61)],
+    )
+    conn.execute("delete from people where id % 3 = 0")
+    conn.execute("delete from notes where id between 20 and 35")
+    conn.close()
+
+
+def types():
+    conn = fresh("types.db")
+    conn.executescript(
+        """
+        create table typed(
+            id integer primary key, name varchar(20), price double, qty bigint, data blob,
+            amount numeric, anything
+        );
+        create table mixed(id integer primary key, label text, a integer, n numeric, x);
+        create table prices(id integer primary key, price numeric);
+        create table pairs(k text primary key, v) without rowid;
+        create table empty_pairs(k text primary key, v) without rowid;
+        """
+    )
+    conn.executemany(
+        "insert into typed(name, price, qty, data, amount, anything) values (?, ?, ?, ?, ?, ?)",
+        [
+            (f"item {i}", i * 1.5, i, bytes([i]), i * 10, None if i < 3 else i)
+            for i in range(10)
+        ],
+    )
+    conn.executemany(
+        "insert into mixed(label, a, n, x) values (?, ?, ?, ?)",
+        [
+            ("one", 1, 1, 1),
+            ("two", "oops", 2.5, "two"),
+            ("three", 3, "abc", b"\x03"),
+            ("four", 4.5, 4, 4),
+        ],
+    )
+    # NUMERIC stores 3.0 as the integer 3, the first value doesn't tell the column type
+    conn.executemany("insert into prices(price) values (?)", [(3.0,), (2.75,)])
+    conn.executemany(
+        "insert into pairs(k, v) values (?, ?)",
+        [(f"key {i:02}", None if i < 3 else i) for i in range(10)],
+    )
+    conn.close()
+
+
+def uncheckpointed_wal():
+    # every change stays in the -wal file, the main file only has the header SQLite
+    # writes when switching to WAL mode, with a text encoding of 0
+    conn = fresh("uncheckpointed.db")
+    conn.execute("pragma journal_mode=wal")
+    conn.execute("pragma wal_autocheckpoint=0")
+    conn.execute("create table t(id integer primary key, name text)")
+    conn.executemany("insert into t(name) values (?)", [(f"name {i}",) for i in range(20)])
+    snapshot(conn, "uncheckpointed.db")
+
+
+def without_rowid():
+    # the records of edges store the primary key first, in (src, dst) order
+    conn = fresh("without_rowid.db")
+    conn.executescript(
+        """
+        create table kv(k text primary key, v integer) without rowid;
+        create table edges(dst integer, src integer, weight real, primary key(src, dst))
+            without rowid;
+        """
+    )
+    conn.executemany("insert into kv(k, v) values (?, ?)", [(f"key {i:03}", i) for i in range(300)])
+    conn.executemany(
+        "insert into edges(dst, src, weight) values (?, ?, ?)",
+        [(i % 7, i // 7, i / 2) for i in range(50)],
+    )
+    conn.close()
+
+
+def wide_schema():
+    # sqlite_schema needs an interior page over its leaves to hold every table
+    conn = fresh("wide_schema.db")
+    for i in range(60):
+        columns = ", ".join(f"column_{j} text" for j in range(8))
+        conn.execute(f"create table table_{i:02}(id integer primary key, {columns})")
+        conn.execute(f"insert into table_{i:02}(column_0) values ('row of table {i}')")
+    conn.close()
+
+
+def snapshot(conn, name, log="-wal"):
+    """Close `conn` keeping the database and its -wal or -journal file as they are while it's
+    open.
+
+    Closing the last connection checkpoints the WAL into the database and deletes it, and
+    rolls back an open transaction.
+    """
+    saved = [(path(name + suffix), path(name + suffix + ".open")) for suffix in ("", log)]
+    for live, copy in saved:
+        shutil.copy(live, copy)
+    conn.close()
+    for live, copy in saved:
+        os.replace(copy, live)
+
+
+FIXTURES = {
+    "aliases": aliases,
+    "altered": altered,
+    "catalog": catalog,
+    "empty": empty,
+    "freeblocks": freeblocks,
+    "freelist": freelist,
+    "history": history,
+    "hot_journal": hot_journal,
+    "overflow": overflow,
+    "people": people,
+    "recover": recover,
+    "types": types,
+    "uncheckpointed": uncheckpointed_wal,
+    "wide_schema": wide_schema,
+    "without_rowid": without_rowid,
+}
+
+
+if __name__ == "__main__":
+    for name in sys.argv[1:] or FIXTURES:
+        FIXTURES[name]()
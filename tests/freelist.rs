@@ -0,0 +1,94 @@
+mod common;
+
+use common::{fixture, page_mut, read_fixture, text};
+use sqlite3_dump::error::{self, CorruptionKind, SQLiteError};
+use sqlite3_dump::freelist::{FreePage, FreePageKind};
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+
+/// rowid and body of every row carved from the free pages
+fn carved(reader: &Reader<Vec<u8>>) -> error::Result<Vec<(i64, String)>> {
+    let mut rows = Vec::new();
+    reader.carve_free_pages(|_, cell, values| {
+        rows.push((cell.rowid as i64, text(&values[1]).unwrap()));
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+fn first_trunk(database: &[u8]) -> u32 {
+    u32::from_be_bytes(database[32..36].try_into().unwrap())
+}
+
+#[test]
+fn lists_trunk_then_leaves() {
+    let reader = Reader::open_mmap(fixture("freelist.db")).unwrap();
+    let freelist = reader.freelist().unwrap();
+    assert_eq!(freelist.len(), reader.header.total_freelist_pages as usize);
+    assert_eq!(
+        freelist[0],
+        FreePage {
+            pageno: reader.header.first_freelist_page_no,
+            kind: FreePageKind::Trunk,
+        }
+    );
+    assert!(freelist[1..]
+        .iter()
+        .all(|page| page.kind == FreePageKind::Leaf));
+}
+
+#[test]
+fn carves_deleted_rows_from_free_leaves() {
+    let reader = Reader::from_source(read_fixture("freelist.db")).unwrap();
+    let rows = carved(&reader).unwrap();
+    for (rowid, body) in &rows {
+        assert_eq!(*body, format!("note {rowid} {}", "x".repeat(40)));
+    }
+
+    // a page may also keep copies of live rows moved off it when the tree was balanced
+    let mut deleted: Vec<_> = rows
+        .iter()
+        .map(|&(rowid, _)| rowid)
+        .filter(|&rowid| rowid <= 150)
+        .collect();
+    deleted.sort();
+    deleted.dedup();
+    // the first deleted rows were on the page that became the freelist trunk and on the
+    // leaf left in the tree
+    assert_eq!(deleted, (28..=150).collect::<Vec<_>>());
+}
+
+#[test]
+fn skips_leaves_past_the_end_of_the_file() {
+    let mut database = read_fixture("freelist.db");
+    let expected = carved(&Reader::from_source(database.clone()).unwrap()).unwrap();
+
+    // list one more leaf, far past the last page
+    let trunk_pageno = first_trunk(&database);
+    let trunk = page_mut(&mut database, PAGE_SIZE, trunk_pageno);
+    let leaves = u32::from_be_bytes(trunk[4..8].try_into().unwrap()) as usize;
+    trunk[4..8].copy_from_slice(&(leaves as u32 + 1).to_be_bytes());
+    trunk[8 + 4 * leaves..12 + 4 * leaves].copy_from_slice(&9999u32.to_be_bytes());
+
+    let reader = Reader::from_source(database).unwrap();
+    assert_eq!(reader.freelist().unwrap().last().unwrap().pageno, 9999);
+    assert_eq!(carved(&reader).unwrap(), expected);
+}
+
+#[test]
+fn trunk_pointing_at_itself() {
+    let mut database = read_fixture("freelist.db");
+    let trunk_pageno = first_trunk(&database);
+    let trunk = page_mut(&mut database, PAGE_SIZE, trunk_pageno);
+    trunk[0..4].copy_from_slice(&trunk_pageno.to_be_bytes());
+
+    let reader = Reader::from_source(database).unwrap();
+    assert!(matches!(
+        carved(&reader),
+        Err(SQLiteError::Corrupt {
+            kind: CorruptionKind::PageCycle,
+            ..
+        })
+    ));
+}
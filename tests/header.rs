@@ -0,0 +1,83 @@
+mod common;
+
+use common::read_fixture;
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::model::{FileFormatVersion, TextEncoding};
+use sqlite3_dump::Reader;
+
+#[test]
+fn header_fields() {
+    let database = read_fixture("people.db");
+    let pages = (database.len() / 512) as u32;
+    let header = Reader::from_source(database).unwrap().header;
+
+    assert_eq!(header.page_size.real_size(), 512);
+    assert_eq!(header.write_version, FileFormatVersion::Legacy);
+    assert_eq!(header.read_version, FileFormatVersion::Legacy);
+    assert_eq!(header.reserved_size, 0);
+    assert_eq!(
+        (
+            header.max_payload_fraction,
+            header.min_payload_fraction,
+            header.leaf_payload_fraction
+        ),
+        (64, 32, 32)
+    );
+    assert_eq!(header.in_header_db_size(), Some(pages));
+    assert_eq!(header.first_freelist_page_no, 0);
+    assert_eq!(header.total_freelist_pages, 0);
+    assert_eq!(header.schema_format_no, 4);
+    assert!(matches!(header.db_text_encoding, TextEncoding::Utf8));
+    assert_eq!(header.user_version, 0);
+    assert!(!header.incremental_vacuum_mode);
+    assert_eq!(header.application_id, 0);
+    assert!(header.sqlite_version_number >= 3_000_000);
+}
+
+#[test]
+fn user_version_and_application_id() {
+    let mut database = read_fixture("people.db");
+    database[60..64].copy_from_slice(&7u32.to_be_bytes());
+    database[68..72].copy_from_slice(&0x0f05_5112u32.to_be_bytes());
+
+    let header = Reader::from_source(database).unwrap().header;
+    assert_eq!(header.user_version, 7);
+    assert_eq!(header.application_id, 0x0f05_5112);
+}
+
+#[test]
+fn stale_database_size() {
+    // a size written by a SQLite older than 3.7.0 isn't trusted
+    let mut database = read_fixture("people.db");
+    database[92..96].copy_from_slice(&0u32.to_be_bytes());
+
+    let header = Reader::from_source(database).unwrap().header;
+    assert_eq!(header.in_header_db_size(), None);
+}
+
+#[test]
+fn not_a_database() {
+    let mut database = read_fixture("people.db");
+    database[..16].copy_from_slice(b"SQLite format 2\0");
+    assert!(Reader::from_source(database).is_err());
+}
+
+#[test]
+fn invalid_page_size() {
+    let mut database = read_fixture("people.db");
+    database[16..18].copy_from_slice(&1000u16.to_be_bytes());
+
+    match Reader::from_source(database) {
+        Err(SQLiteError::Corrupt { kind, .. }) => {
+            assert_eq!(kind, CorruptionKind::InvalidPageSize(1000))
+        }
+        other => panic!("expected an invalid page size, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn unknown_text_encoding() {
+    let mut database = read_fixture("people.db");
+    database[56..60].copy_from_slice(&7u32.to_be_bytes());
+    assert!(Reader::from_source(database).is_err());
+}
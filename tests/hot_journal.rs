@@ -0,0 +1,150 @@
+mod common;
+
+use std::path::PathBuf;
+
+use common::{fixture, read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::journal::{HotJournal, Journal};
+use sqlite3_dump::{Pager, Reader};
+
+/// pages saved in the journal, every spill of the cache wrote a segment with one of them
+const JOURNAL_RECORDS: usize = 7;
+const PAGE_SIZE: usize = 512;
+/// a segment header takes a whole sector
+const SECTOR_SIZE: usize = 512;
+const JOURNAL_MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+
+fn names(reader: &Reader<impl Pager>) -> Vec<String> {
+    let mut names = Vec::new();
+    reader
+        .stream_table_rows_sequential("t", |_, values| {
+            names.push(text(&values[1]).unwrap());
+            Ok(())
+        })
+        .unwrap();
+    names
+}
+
+fn before() -> Vec<String> {
+    (0..200).map(|i| format!("before {i}")).collect()
+}
+
+fn rolled_back(journal: Vec<u8>) -> Reader<Vec<u8>> {
+    Reader::from_source(read_fixture("hot.db"))
+        .unwrap()
+        .with_journal(Journal::from_source(journal).unwrap(), HotJournal::Rollback)
+        .unwrap()
+}
+
+/// hot.db copied under the target directory as `name` next to `journal`
+fn copy_with_journal(name: &str, journal: &[u8]) -> PathBuf {
+    let database = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::copy(fixture("hot.db"), &database).unwrap();
+    std::fs::write(format!("{}-journal", database.display()), journal).unwrap();
+    database
+}
+
+#[test]
+fn warns_and_reads_the_half_written_file() {
+    let reader = Reader::open_mmap(fixture("hot.db")).unwrap();
+    let journal = reader.hot_journal().unwrap();
+    assert_eq!(journal.record_count(), JOURNAL_RECORDS);
+    assert_eq!(names(&reader)[0], "after 0");
+}
+
+#[test]
+fn rolls_back_to_the_last_commit() {
+    let reader = Reader::open_mmap(fixture("hot.db"))
+        .unwrap()
+        .on_hot_journal(HotJournal::Rollback)
+        .unwrap();
+    assert_eq!(names(&reader), before());
+
+    // and back to the file as it is
+    let reader = reader.on_hot_journal(HotJournal::Warn).unwrap();
+    assert_eq!(names(&reader)[0], "after 0");
+}
+
+#[test]
+fn refuses_a_hot_journal() {
+    let reader = Reader::open_pread(fixture("hot.db"), 4).unwrap();
+    assert!(matches!(
+        reader.on_hot_journal(HotJournal::Refuse),
+        Err(SQLiteError::HotJournal(JOURNAL_RECORDS))
+    ));
+}
+
+#[test]
+fn playback_stops_at_a_bad_checksum() {
+    // the checksum covers every 200th byte counted back from the end of the page
+    let mut journal = read_fixture("hot.db-journal");
+    let last_segment = journal
+        .windows(JOURNAL_MAGIC.len())
+        .rposition(|magic| magic == JOURNAL_MAGIC)
+        .unwrap();
+    let last = last_segment + SECTOR_SIZE;
+    journal[last + 4 + PAGE_SIZE % 200] ^= 0xff;
+
+    let reader = rolled_back(journal);
+    assert_eq!(
+        reader.hot_journal().unwrap().record_count(),
+        JOURNAL_RECORDS - 1
+    );
+    let names = names(&reader);
+    assert_eq!(names[0], "before 0");
+    assert!(names.iter().any(|name| name.starts_with("after")));
+}
+
+#[test]
+fn zeroed_header_is_not_hot() {
+    let mut journal = read_fixture("hot.db-journal");
+    journal[..28].fill(0);
+    assert!(Journal::from_source(journal.clone()).is_none());
+
+    let database = copy_with_journal("zeroed_journal.db", &journal);
+    let reader = Reader::open_mmap(database).unwrap();
+    assert!(reader.hot_journal().is_none());
+}
+
+#[test]
+fn journal_of_another_page_size() {
+    let mut journal = read_fixture("hot.db-journal");
+    journal[24..28].copy_from_slice(&1024u32.to_be_bytes());
+
+    let reader = Reader::from_source(read_fixture("hot.db")).unwrap();
+    let journal = Journal::from_source(journal).unwrap();
+    assert!(matches!(
+        reader.with_journal(journal, HotJournal::Rollback),
+        Err(SQLiteError::Other(_))
+    ));
+}
+
+#[test]
+fn rollback_drops_the_pages_the_transaction_appended() {
+    // point the table at the last page of the file, the inserts appended it
+    let mut database = read_fixture("hot.db");
+    let appended = (database.len() / PAGE_SIZE) as u32;
+    database[PAGE_SIZE + 8..PAGE_SIZE + 12].copy_from_slice(&appended.to_be_bytes());
+
+    let reader = Reader::from_source(database).unwrap();
+    let journal = Journal::from_source(read_fixture("hot.db-journal")).unwrap();
+    assert!(appended > journal.db_size());
+
+    let pages = reader.page_kinds().unwrap().len();
+    let db_size = journal.db_size() as usize;
+    assert!(
+        pages > db_size,
+        "{pages} pages, {db_size} before the transaction"
+    );
+
+    let reader = reader.with_journal(journal, HotJournal::Rollback).unwrap();
+    assert_eq!(reader.page_kinds().unwrap().len(), db_size);
+    assert!(matches!(
+        reader.stream_table_rows_sequential("t", |_, _| Ok(())),
+        Err(SQLiteError::Corrupt {
+            page,
+            kind: CorruptionKind::PageOutOfRange,
+            ..
+        }) if page == appended
+    ));
+}
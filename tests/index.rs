@@ -0,0 +1,53 @@
+mod common;
+
+use common::{page_mut, read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::model::Payload;
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// root page of the people_name index, an interior page over its leaves
+const PEOPLE_NAME_ROOT: u32 = 3;
+
+fn index_entries(database: Vec<u8>) -> Result<Vec<(Option<String>, i64)>, SQLiteError> {
+    let reader = Reader::from_source(database)?;
+    let mut entries = Vec::new();
+    reader.stream_index_entries("people_name", |values| {
+        let rowid = match values[1] {
+            Some(Payload::I64(rowid)) => rowid,
+            ref other => panic!("expected a rowid, got {other:?}"),
+        };
+        entries.push((text(&values[0]), rowid));
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+#[test]
+fn entries_in_key_order() {
+    let mut expected: Vec<_> = (0..200)
+        .map(|i| (Some(format!("person {i}")), i + 1))
+        .collect();
+    expected.sort();
+    assert_eq!(index_entries(read_fixture("people.db")).unwrap(), expected);
+}
+
+#[test]
+fn missing_index() {
+    let reader = Reader::from_source(read_fixture("people.db")).unwrap();
+    assert!(reader.stream_index_entries("people", |_| Ok(())).is_err());
+}
+
+#[test]
+fn table_page_in_an_index_tree() {
+    let mut database = read_fixture("people.db");
+    page_mut(&mut database, PAGE_SIZE, PEOPLE_NAME_ROOT)[0] = 0x05;
+
+    match index_entries(database) {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => {
+            assert_eq!(page, PEOPLE_NAME_ROOT);
+            assert_eq!(kind, CorruptionKind::InvalidPageType(0x05));
+        }
+        other => panic!("expected an invalid page type, got {other:?}"),
+    }
+}
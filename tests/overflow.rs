@@ -0,0 +1,86 @@
+mod common;
+
+use common::{page_mut, read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::model::Payload;
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// first page of the overflow chain of the body of doc 2
+const BODY_OVERFLOW: u32 = 4;
+
+fn body() -> String {
+    (0..400).map(|i| format!("line {i}\n")).collect()
+}
+
+fn data() -> Vec<u8> {
+    (0..8).flat_map(|_| 0..=255u8).collect()
+}
+
+/// rowid, title, body and data of every doc
+type Doc = (i64, Option<String>, Option<String>, Option<Vec<u8>>);
+
+fn docs(database: Vec<u8>) -> Result<Vec<Doc>, SQLiteError> {
+    let reader = Reader::from_source(database)?;
+    let mut rows = Vec::new();
+    reader.stream_table_rows_sequential("docs", |cell, values| {
+        let data = match values[3] {
+            Some(Payload::Blob(data)) => Some(data.to_vec()),
+            None => None,
+            ref other => panic!("expected a blob, got {other:?}"),
+        };
+        rows.push((cell.rowid as i64, text(&values[1]), text(&values[2]), data));
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+#[test]
+fn spilled_columns() {
+    let expected = vec![
+        (1, Some("short".into()), Some("hello".into()), Some(vec![1])),
+        (2, Some("long body".into()), Some(body()), None),
+        (3, Some("t".repeat(1000)), None, Some(data())),
+    ];
+    assert_eq!(docs(read_fixture("overflow.db")).unwrap(), expected);
+}
+
+#[test]
+fn spilled_index_entry() {
+    let reader = Reader::from_source(read_fixture("overflow.db")).unwrap();
+    let mut titles = Vec::new();
+    reader
+        .stream_index_entries("docs_title", |values| {
+            titles.push(text(&values[0]).unwrap());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(titles, ["long body", "short", &"t".repeat(1000)]);
+}
+
+#[test]
+fn overflow_page_past_the_end() {
+    let mut database = read_fixture("overflow.db");
+    page_mut(&mut database, PAGE_SIZE, BODY_OVERFLOW)[..4].copy_from_slice(&9999u32.to_be_bytes());
+
+    match docs(database) {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => {
+            assert_eq!(page, 9999);
+            assert_eq!(kind, CorruptionKind::PageOutOfRange);
+        }
+        other => panic!("expected a page out of range, got {other:?}"),
+    }
+}
+
+#[test]
+fn overflow_chain_ends_early() {
+    let mut database = read_fixture("overflow.db");
+    page_mut(&mut database, PAGE_SIZE, BODY_OVERFLOW)[..4].copy_from_slice(&0u32.to_be_bytes());
+    assert!(matches!(
+        docs(database),
+        Err(SQLiteError::Corrupt {
+            kind: CorruptionKind::Malformed,
+            ..
+        })
+    ));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use common::{fixture, read_fixture, text};
+use sqlite3_dump::error::{self, CorruptionKind, SQLiteError};
+use sqlite3_dump::{PageRef, Pager, PreadPager, Reader};
+
+fn names<P: Pager>(reader: &Reader<P>) -> error::Result<Vec<String>> {
+    let mut names = Vec::new();
+    reader.stream_table_rows_sequential("people", |_, values| {
+        names.push(text(&values[1]).unwrap());
+        Ok(())
+    })?;
+    Ok(names)
+}
+
+/// an in-memory pager that counts the pages it hands out
+struct CountingPager<'a> {
+    database: Vec<u8>,
+    reads: &'a AtomicUsize,
+}
+
+impl Pager for CountingPager<'_> {
+    fn page(&self, pageno: u32, page_size: usize) -> error::Result<Option<PageRef<'_>>> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.database.page(pageno, page_size)
+    }
+}
+
+#[test]
+fn every_backend_reads_the_same_rows() {
+    let expected = names(&Reader::open_mmap(fixture("people.db")).unwrap()).unwrap();
+    assert_eq!(expected.len(), 200);
+
+    let in_memory = Reader::from_source(read_fixture("people.db")).unwrap();
+    assert_eq!(names(&in_memory).unwrap(), expected);
+
+    for cache_pages in [0, 1, 2, PreadPager::DEFAULT_CACHE_PAGES] {
+        let pread = Reader::open_pread(fixture("people.db"), cache_pages).unwrap();
+        assert_eq!(names(&pread).unwrap(), expected);
+    }
+
+    let reads = AtomicUsize::new(0);
+    let pager = CountingPager {
+        database: read_fixture("people.db"),
+        reads: &reads,
+    };
+    let custom = Reader::from_source(pager).unwrap();
+    assert_eq!(names(&custom).unwrap(), expected);
+    assert!(reads.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn page_zero_is_no_page() {
+    let database = read_fixture("people.db");
+    assert!(database.page(0, 512).unwrap().is_none());
+    assert!(database.page(1, 512).unwrap().is_some());
+
+    let pread = PreadPager::open(fixture("people.db")).unwrap();
+    assert!(pread.page(0, 512).unwrap().is_none());
+    assert!(pread.page(1, 512).unwrap().is_some());
+}
+
+#[test]
+fn pages_past_the_end_of_the_file() {
+    let database = read_fixture("people.db");
+    let last = (database.len() / 512) as u32;
+    assert!(database.page(last, 512).unwrap().is_some());
+    assert!(database.page(last + 1, 512).unwrap().is_none());
+
+    let pread = PreadPager::open(fixture("people.db")).unwrap();
+    assert!(pread.page(last, 512).unwrap().is_some());
+    assert!(pread.page(last + 1, 512).unwrap().is_none());
+}
+
+#[test]
+fn truncated_file_through_pread() {
+    let database = read_fixture("people.db");
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&database[..4 * 512]).unwrap();
+
+    let reader = Reader::open_pread(file.path(), 16).unwrap();
+    assert!(matches!(
+        names(&reader),
+        Err(SQLiteError::Corrupt {
+            kind: CorruptionKind::PageOutOfRange,
+            ..
+        })
+    ));
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::ops::RangeBounds;
+
+use common::{cell_offset, page_mut, read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// root page of the people table, an interior page over its leaves
+const PEOPLE_ROOT: u32 = 2;
+
+/// rowid and name of a person
+type Person = (i64, Option<String>);
+
+fn people() -> Vec<Person> {
+    (0..200)
+        .map(|i| (i + 1, Some(format!("person {i}"))))
+        .collect()
+}
+
+/// every row of people by partition, scanned on 3 threads
+fn scan(database: Vec<u8>, ordered: bool) -> Result<Vec<(usize, Person)>, SQLiteError> {
+    let reader = Reader::from_source(database)?;
+    let mut rows = Vec::new();
+    reader.stream_table_rows_parallel(
+        "people",
+        3,
+        ordered,
+        |cell, values| Ok((cell.rowid as i64, text(&values[1]))),
+        |partition, row| {
+            rows.push((partition, row));
+            Ok(())
+        },
+    )?;
+    Ok(rows)
+}
+
+#[test]
+fn partitions_cover_every_rowid_once() {
+    let reader = Reader::from_source(read_fixture("people.db")).unwrap();
+    let partitions = reader.table_partitions("people", 4).unwrap();
+    assert!(partitions.len() > 1 && partitions.len() <= 4);
+    for rowid in 1..=200 {
+        let holders = partitions
+            .iter()
+            .filter(|range| range.contains(&rowid))
+            .count();
+        assert_eq!(holders, 1, "rowid {rowid}");
+    }
+}
+
+#[test]
+fn ordered_scan() {
+    let rows = scan(read_fixture("people.db"), true).unwrap();
+    assert!(rows.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    let rows: Vec<_> = rows.into_iter().map(|(_, row)| row).collect();
+    assert_eq!(rows, people());
+}
+
+#[test]
+fn unordered_scan() {
+    let mut partitions: BTreeMap<usize, Vec<_>> = BTreeMap::new();
+    for (partition, row) in scan(read_fixture("people.db"), false).unwrap() {
+        partitions.entry(partition).or_default().push(row);
+    }
+    assert!(partitions.len() > 1);
+    // rows of one partition come in rowid order
+    let rows: Vec<_> = partitions.into_values().flatten().collect();
+    assert_eq!(rows, people());
+}
+
+#[test]
+fn consumer_error_stops_the_scan() {
+    let reader = Reader::from_source(read_fixture("people.db")).unwrap();
+    let mut seen = 0;
+    let result = reader.stream_table_rows_parallel(
+        "people",
+        3,
+        false,
+        |cell, _| Ok(cell.rowid),
+        |_, _| {
+            seen += 1;
+            Err(SQLiteError::Other("enough".into()))
+        },
+    );
+    assert!(matches!(result, Err(SQLiteError::Other(message)) if message == "enough"));
+    assert_eq!(seen, 1);
+}
+
+#[test]
+fn damaged_leaf() {
+    let mut database = read_fixture("people.db");
+    let root = page_mut(&mut database, PAGE_SIZE, PEOPLE_ROOT);
+    let cell = cell_offset(root, 1);
+    let leaf = u32::from_be_bytes(root[cell..cell + 4].try_into().unwrap());
+    page_mut(&mut database, PAGE_SIZE, leaf)[0] = 0xff;
+
+    for ordered in [true, false] {
+        match scan(database.clone(), ordered) {
+            Err(SQLiteError::Corrupt { page, kind, .. }) => {
+                assert_eq!(page, leaf);
+                assert_eq!(kind, CorruptionKind::InvalidPageType(0xff));
+            }
+            other => panic!("expected an invalid page type, got {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn missing_table() {
+    let reader = Reader::from_source(read_fixture("people.db")).unwrap();
+    assert!(matches!(
+        reader.table_partitions("nobody", 4),
+        Err(SQLiteError::TableNotFound(_))
+    ));
+}
@@ -0,0 +1,225 @@
+mod common;
+
+use std::fs::File;
+
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::datatypes::{DataType, Float64Type, Int64Type};
+use common::{fixture, page_mut, read_fixture};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::parquet_writer::{export_table, mixed_data_type, Exported, MixedTypes};
+use sqlite3_dump::Reader;
+
+/// export `table` of types.db and read the whole file back as one batch
+fn export(table: &str, mixed_types: MixedTypes) -> Result<RecordBatch, SQLiteError> {
+    export_counted(table, mixed_types).map(|(batch, _)| batch)
+}
+
+/// like [`export`], with what [`export_table`] reported
+fn export_counted(
+    table: &str,
+    mixed_types: MixedTypes,
+) -> Result<(RecordBatch, Exported), SQLiteError> {
+    let reader = Reader::open_mmap(fixture("types.db"))?;
+    let dir = tempfile::tempdir()?;
+    let output = dir.path().join("out.parquet");
+    let exported = export_table(&reader, table, &output, 4, mixed_types)?;
+
+    let batches = ParquetRecordBatchReaderBuilder::try_new(File::open(&output)?)
+        .unwrap()
+        .build()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let schema = batches
+        .first()
+        .map_or_else(|| reader_schema(&output), |batch| batch.schema());
+    let batch = arrow::compute::concat_batches(&schema, &batches).unwrap();
+    Ok((batch, exported))
+}
+
+fn reader_schema(output: &std::path::Path) -> arrow::datatypes::SchemaRef {
+    ParquetRecordBatchReaderBuilder::try_new(File::open(output).unwrap())
+        .unwrap()
+        .schema()
+        .clone()
+}
+
+fn columns(batch: &RecordBatch) -> Vec<(String, DataType)> {
+    batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| (field.name().clone(), field.data_type().clone()))
+        .collect()
+}
+
+fn integers(array: &dyn Array) -> Vec<Option<i64>> {
+    array.as_primitive::<Int64Type>().iter().collect()
+}
+
+fn reals(array: &dyn Array) -> Vec<Option<f64>> {
+    array.as_primitive::<Float64Type>().iter().collect()
+}
+
+#[test]
+fn lossless_keeps_every_value_of_mixed_columns() {
+    let batch = export("mixed", MixedTypes::Lossless).unwrap();
+    assert_eq!(
+        columns(&batch),
+        [
+            ("id".to_owned(), DataType::Int64),
+            ("label".to_owned(), DataType::Utf8),
+            ("a".to_owned(), mixed_data_type()),
+            ("n".to_owned(), mixed_data_type()),
+            ("x".to_owned(), mixed_data_type()),
+        ]
+    );
+
+    let a = batch.column_by_name("a").unwrap().as_struct();
+    assert_eq!(integers(a.column(0)), [Some(1), None, Some(3), None]);
+    assert_eq!(reals(a.column(1)), [None, None, None, Some(4.5)]);
+    let texts: Vec<_> = a.column(2).as_string::<i32>().iter().collect();
+    assert_eq!(texts, [None, Some("oops"), None, None]);
+
+    let x = batch.column_by_name("x").unwrap().as_struct();
+    let blobs: Vec<_> = x.column(3).as_binary::<i32>().iter().collect();
+    assert_eq!(blobs, [None, None, Some(&[3u8][..]), None]);
+}
+
+#[test]
+fn coerce_writes_null_for_values_that_dont_fit_and_counts_them() {
+    let (batch, exported) = export_counted("mixed", MixedTypes::Coerce).unwrap();
+    assert_eq!(
+        integers(batch.column_by_name("a").unwrap()),
+        [Some(1), None, Some(3), Some(4)]
+    );
+    assert_eq!(
+        integers(batch.column_by_name("x").unwrap()),
+        [Some(1), None, None, Some(4)]
+    );
+    // "oops" and 4.5 in a, "two" and the blob in x
+    assert_eq!(exported.rows, 4);
+    assert_eq!(exported.coerced, 4);
+}
+
+#[test]
+fn numeric_columns_holding_text_are_mixed() {
+    for mixed_types in [MixedTypes::Coerce, MixedTypes::Lossless] {
+        let batch = export("mixed", mixed_types).unwrap();
+        let n = batch.column_by_name("n").unwrap().as_struct();
+        assert_eq!(n.data_type(), &mixed_data_type());
+        assert_eq!(integers(n.column(0)), [Some(1), None, None, Some(4)]);
+        assert_eq!(reals(n.column(1)), [None, Some(2.5), None, None]);
+        let texts: Vec<_> = n.column(2).as_string::<i32>().iter().collect();
+        assert_eq!(texts, [None, None, Some("abc"), None]);
+    }
+}
+
+#[test]
+fn strict_fails_on_the_first_value_that_doesnt_fit() {
+    match export("mixed", MixedTypes::Strict) {
+        Err(SQLiteError::TypeMismatch {
+            rowid,
+            column,
+            found,
+            ..
+        }) => {
+            assert_eq!(rowid, Some(2));
+            assert_eq!(column, "a");
+            assert_eq!(found, "TEXT");
+        }
+        other => panic!("expected a type mismatch, got {other:?}"),
+    }
+}
+
+#[test]
+fn declared_types_decide_through_their_affinity() {
+    let batch = export("typed", MixedTypes::Coerce).unwrap();
+    assert_eq!(
+        columns(&batch),
+        [
+            ("id".to_owned(), DataType::Int64),
+            ("name".to_owned(), DataType::Utf8),
+            ("price".to_owned(), DataType::Float64),
+            ("qty".to_owned(), DataType::Int64),
+            ("data".to_owned(), DataType::Binary),
+            ("amount".to_owned(), DataType::Float64),
+            ("anything".to_owned(), DataType::Int64),
+        ]
+    );
+    assert_eq!(batch.num_rows(), 10);
+}
+
+#[test]
+fn numeric_columns_are_reals_whatever_their_first_value() {
+    let batch = export("prices", MixedTypes::Coerce).unwrap();
+    assert_eq!(columns(&batch)[1], ("price".to_owned(), DataType::Float64));
+    assert_eq!(
+        reals(batch.column_by_name("price").unwrap()),
+        [Some(3.0), Some(2.75)]
+    );
+}
+
+#[test]
+fn without_rowid_columns_are_sampled_past_the_first_row() {
+    let batch = export("pairs", MixedTypes::Coerce).unwrap();
+    assert_eq!(
+        columns(&batch),
+        [
+            ("k".to_owned(), DataType::Utf8),
+            ("v".to_owned(), DataType::Int64),
+        ]
+    );
+    let expected: Vec<_> = (0..10).map(|i| (i >= 3).then_some(i)).collect();
+    assert_eq!(integers(batch.column_by_name("v").unwrap()), expected);
+}
+
+#[test]
+fn empty_without_rowid_table() {
+    for mixed_types in [MixedTypes::Coerce, MixedTypes::Lossless, MixedTypes::Strict] {
+        let batch = export("empty_pairs", mixed_types).unwrap();
+        assert_eq!(batch.num_rows(), 0);
+        assert_eq!(
+            columns(&batch),
+            [
+                ("k".to_owned(), DataType::Utf8),
+                ("v".to_owned(), DataType::Binary),
+            ]
+        );
+    }
+}
+
+#[test]
+fn missing_table() {
+    assert!(matches!(
+        export("nope", MixedTypes::Coerce),
+        Err(SQLiteError::TableNotFound(_))
+    ));
+}
+
+#[test]
+fn damaged_leaf_fails_the_export() {
+    // the last leaf of people
+    let mut database = read_fixture("people.db");
+    let root = page_mut(&mut database, 512, 2);
+    let leaf = u32::from_be_bytes(root[8..12].try_into().unwrap());
+    page_mut(&mut database, 512, leaf)[0] = 0xff;
+
+    let reader = Reader::from_source(database).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let result = export_table(
+        &reader,
+        "people",
+        dir.path().join("out.parquet"),
+        4,
+        MixedTypes::Coerce,
+    );
+    match result {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => {
+            assert_eq!(page, leaf);
+            assert_eq!(kind, CorruptionKind::InvalidPageType(0xff));
+        }
+        other => panic!("expected an invalid page type, got {other:?}"),
+    }
+}
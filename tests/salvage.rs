@@ -0,0 +1,114 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{fixture, page_mut, read_fixture, text};
+use sqlite3_dump::salvage::PageKind;
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+
+/// rows salvaged from each table, `lost_and_found` for the rows of no known table,
+/// sqlite_schema left out
+fn salvage(database: Vec<u8>) -> BTreeMap<String, Vec<(i64, Option<String>)>> {
+    let reader = Reader::salvage_source(database).unwrap();
+    let mut tables: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    reader
+        .salvage(|row, values| {
+            let table = row.table.unwrap_or("lost_and_found");
+            if table != "sqlite_schema" {
+                let name = values.get(1).and_then(text);
+                tables
+                    .entry(table.to_owned())
+                    .or_default()
+                    .push((row.rowid, name));
+            }
+            Ok(())
+        })
+        .unwrap();
+    tables
+}
+
+fn people() -> Vec<(i64, Option<String>)> {
+    (0..200)
+        .map(|i| (i + 1, Some(format!("person {i}"))))
+        .collect()
+}
+
+#[test]
+fn healthy_database() {
+    let tables = salvage(read_fixture("people.db"));
+    assert_eq!(tables.keys().collect::<Vec<_>>(), ["people"]);
+    assert_eq!(tables["people"], people());
+}
+
+#[test]
+fn page_kinds() {
+    let reader = Reader::open_mmap(fixture("people.db")).unwrap();
+    let kinds = reader.page_kinds().unwrap();
+    assert_eq!(kinds.len(), 20);
+    assert_eq!(kinds[0], PageKind::LeafTable);
+    assert_eq!(kinds[1], PageKind::InteriorTable);
+    assert_eq!(kinds[2], PageKind::InteriorIndex);
+}
+
+#[test]
+fn lost_database_header() {
+    let mut database = read_fixture("people.db");
+    database[..100].fill(0);
+    assert!(Reader::from_source(database.clone()).is_err());
+
+    let reader = Reader::salvage_source(database.clone()).unwrap();
+    assert_eq!(reader.header.page_size.real_size(), PAGE_SIZE);
+    let tables = salvage(database);
+    assert_eq!(tables["people"], people());
+}
+
+#[test]
+fn lost_root_page() {
+    let mut database = read_fixture("people.db");
+    page_mut(&mut database, PAGE_SIZE, 2).fill(0);
+
+    let tables = salvage(database);
+    assert_eq!(tables.keys().collect::<Vec<_>>(), ["lost_and_found"]);
+    let mut rows = tables["lost_and_found"].clone();
+    rows.sort();
+    assert_eq!(rows, people());
+}
+
+#[test]
+fn no_database_at_all() {
+    // xorshift, anything but b-tree pages
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let noise: Vec<u8> = (0..64 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect();
+    assert!(salvage(noise).is_empty());
+}
+
+#[test]
+fn lost_database_header_with_pread() {
+    let mut database = read_fixture("people.db");
+    database[..100].fill(0);
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("lost_header.db");
+    std::fs::write(&path, database).unwrap();
+
+    assert!(Reader::open_pread(&path, 4).is_err());
+    let reader = Reader::salvage_pread(&path, 4).unwrap();
+    assert_eq!(reader.header.page_size.real_size(), PAGE_SIZE);
+    let mut rowids = Vec::new();
+    reader
+        .salvage(|row, _| {
+            if row.table == Some("people") {
+                rowids.push(row.rowid);
+            }
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(rowids, (1..=200).collect::<Vec<_>>());
+}
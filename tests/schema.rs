@@ -0,0 +1,69 @@
+mod common;
+
+use common::{page_mut, read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+
+/// the rightmost pointer of page 1, an interior page of sqlite_schema
+fn schema_rightmost(database: &mut [u8]) -> &mut [u8] {
+    &mut page_mut(database, PAGE_SIZE, 1)[108..112]
+}
+
+fn corruption(database: Vec<u8>) -> (u32, CorruptionKind) {
+    let reader = Reader::from_source(database).unwrap();
+    match reader.catalog() {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => (page, kind),
+        other => panic!(
+            "expected a corruption error, got {:?}",
+            other.map(|objects| objects.len())
+        ),
+    }
+}
+
+#[test]
+fn every_table_in_order() {
+    let reader = Reader::from_source(read_fixture("wide_schema.db")).unwrap();
+    let names: Vec<_> = reader
+        .catalog()
+        .unwrap()
+        .iter()
+        .map(|object| object.name.clone())
+        .collect();
+    let expected: Vec<_> = (0..60).map(|i| format!("table_{i:02}")).collect();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn table_under_the_last_schema_leaf() {
+    let reader = Reader::from_source(read_fixture("wide_schema.db")).unwrap();
+    let mut rows = Vec::new();
+    reader
+        .stream_table_rows_sequential("table_59", |_, values| {
+            rows.push(text(&values[1]));
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(rows, [Some("row of table 59".to_owned())]);
+}
+
+#[test]
+fn damaged_schema_leaf() {
+    let mut database = read_fixture("wide_schema.db");
+    let leaf = u32::from_be_bytes(schema_rightmost(&mut database).try_into().unwrap());
+    page_mut(&mut database, PAGE_SIZE, leaf)[0] = 0xff;
+
+    assert_eq!(
+        corruption(database),
+        (leaf, CorruptionKind::InvalidPageType(0xff))
+    );
+}
+
+#[test]
+fn schema_tree_loops() {
+    let mut database = read_fixture("wide_schema.db");
+    schema_rightmost(&mut database).copy_from_slice(&1u32.to_be_bytes());
+
+    assert_eq!(corruption(database), (1, CorruptionKind::PageCycle));
+}
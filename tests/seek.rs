@@ -0,0 +1,82 @@
+mod common;
+
+use std::ops::RangeBounds;
+
+use common::{cell_offset, page_mut, read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// root page of the people table, an interior page over its leaves
+const PEOPLE_ROOT: u32 = 2;
+
+fn rowids(database: Vec<u8>, range: impl RangeBounds<i64>) -> Result<Vec<i64>, SQLiteError> {
+    let reader = Reader::from_source(database)?;
+    let mut rowids = Vec::new();
+    reader.stream_table_rows_range("people", range, |cell, _| {
+        rowids.push(cell.rowid as i64);
+        Ok(())
+    })?;
+    Ok(rowids)
+}
+
+/// people.db with the type byte of the leaf holding rowid 1 trashed
+fn first_leaf_trashed() -> Vec<u8> {
+    let mut database = read_fixture("people.db");
+    let root = page_mut(&mut database, PAGE_SIZE, PEOPLE_ROOT);
+    let cell = cell_offset(root, 0);
+    let leaf = u32::from_be_bytes(root[cell..cell + 4].try_into().unwrap());
+    page_mut(&mut database, PAGE_SIZE, leaf)[0] = 0xff;
+    database
+}
+
+#[test]
+fn rowid_ranges() {
+    let database = read_fixture("people.db");
+    assert_eq!(
+        rowids(database.clone(), 50..=60).unwrap(),
+        (50..=60).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        rowids(database.clone(), ..5).unwrap(),
+        (1..5).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        rowids(database.clone(), 195..).unwrap(),
+        (195..=200).collect::<Vec<_>>()
+    );
+    assert!(rowids(database, 300..400).unwrap().is_empty());
+}
+
+#[test]
+fn row_by_rowid() {
+    let reader = Reader::from_source(read_fixture("people.db")).unwrap();
+    let name = |rowid| {
+        reader
+            .get_row("people", rowid, |_, values| Ok(text(&values[1])))
+            .unwrap()
+    };
+    assert_eq!(name(123), Some(Some("person 122".to_owned())));
+    assert_eq!(name(0), None);
+    assert_eq!(name(201), None);
+    assert!(reader.get_row("nobody", 1, |_, _| Ok(())).is_err());
+}
+
+#[test]
+fn range_skips_damaged_subtree() {
+    // only the subtrees that can hold the range are read
+    assert_eq!(
+        rowids(first_leaf_trashed(), 150..=160).unwrap(),
+        (150..=160).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn range_in_damaged_subtree() {
+    match rowids(first_leaf_trashed(), 1..=3) {
+        Err(SQLiteError::Corrupt { kind, .. }) => {
+            assert_eq!(kind, CorruptionKind::InvalidPageType(0xff))
+        }
+        other => panic!("expected an invalid page type, got {other:?}"),
+    }
+}
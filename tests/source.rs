@@ -0,0 +1,84 @@
+mod common;
+
+use std::io::{self, Read};
+use std::sync::Arc;
+
+use common::{read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::{Pager, Reader};
+
+fn names(reader: &Reader<impl Pager>) -> Vec<Option<String>> {
+    let mut names = Vec::new();
+    reader
+        .stream_table_rows_sequential("people", |_, values| {
+            names.push(text(&values[1]));
+            Ok(())
+        })
+        .unwrap();
+    names
+}
+
+fn people() -> Vec<Option<String>> {
+    (0..200).map(|i| Some(format!("person {i}"))).collect()
+}
+
+/// a reader that hands out a few bytes at a time and then fails
+struct Failing<'a>(&'a [u8]);
+
+impl Read for Failing<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.0.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "gone"));
+        }
+        let n = buf.len().min(self.0.len()).min(7);
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn byte_sources() {
+    let database = read_fixture("people.db");
+    assert_eq!(
+        names(&Reader::from_source(&database[..]).unwrap()),
+        people()
+    );
+    let shared: Arc<[u8]> = database.clone().into();
+    assert_eq!(names(&Reader::from_source(shared).unwrap()), people());
+    assert_eq!(names(&Reader::from_source(database).unwrap()), people());
+}
+
+#[test]
+fn read_from_a_stream() {
+    let database = read_fixture("people.db");
+    let (head, tail) = database.split_at(1000);
+    let reader = Reader::read_from(head.chain(tail)).unwrap();
+    assert_eq!(names(&reader), people());
+}
+
+#[test]
+fn empty_source() {
+    match Reader::from_source(Vec::new()) {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => {
+            assert_eq!(page, 1);
+            assert_eq!(kind, CorruptionKind::PageOutOfRange);
+        }
+        other => panic!("expected page 1 to be missing, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn source_shorter_than_the_header() {
+    let database = read_fixture("people.db");
+    assert!(Reader::read_from(&database[..50]).is_err());
+}
+
+#[test]
+fn stream_fails_midway() {
+    let database = read_fixture("people.db");
+    match Reader::read_from(Failing(&database[..2000])) {
+        Err(SQLiteError::IOError(err)) => assert_eq!(err.kind(), io::ErrorKind::BrokenPipe),
+        other => panic!("expected an I/O error, got {:?}", other.err()),
+    }
+}
@@ -0,0 +1,171 @@
+mod common;
+
+use common::{fixture, read_fixture, text};
+use sqlite3_dump::diff::RowChange;
+use sqlite3_dump::error::SQLiteError;
+use sqlite3_dump::model::Payload;
+use sqlite3_dump::wal::Wal;
+use sqlite3_dump::{Reader, TableRow};
+
+#[test]
+fn opens_database_only_written_to_its_wal() {
+    let reader = Reader::open_mmap(fixture("uncheckpointed.db")).unwrap();
+    assert!(reader.get_tables_map().unwrap().contains_key("t"));
+
+    let mut names = Vec::new();
+    reader
+        .stream_table_rows_sequential("t", |cell, values| {
+            assert_eq!(values[0], Some(Payload::I64(cell.rowid as i64)));
+            names.push(text(&values[1]));
+            Ok(())
+        })
+        .unwrap();
+    let expected: Vec<_> = (0..20).map(|i| Some(format!("name {i}"))).collect();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn main_file_alone_has_no_tables() {
+    let database = read_fixture("uncheckpointed.db");
+    let reader = Reader::from_source(database).unwrap();
+    assert!(reader.get_tables_map().unwrap().is_empty());
+}
+
+#[test]
+fn commit_with_bad_checksum_is_ignored() {
+    let database = read_fixture("uncheckpointed.db");
+    let mut wal = read_fixture("uncheckpointed.db-wal");
+    let last = wal.len() - 1;
+    wal[last] ^= 0xff;
+
+    let reader = Reader::from_source(database)
+        .unwrap()
+        .with_wal(Wal::from_source(wal).unwrap())
+        .unwrap();
+    let mut rows = 0;
+    reader
+        .stream_table_rows_sequential("t", |_, _| {
+            rows += 1;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(rows, 19);
+}
+
+fn names_at(commit: usize) -> Vec<(i64, String)> {
+    let reader = Reader::open_mmap(fixture("history.db"))
+        .unwrap()
+        .at_commit(commit)
+        .unwrap();
+    let mut rows = Vec::new();
+    reader
+        .stream_table_rows_sequential("t", |cell, values| {
+            rows.push((cell.rowid as i64, text(&values[1]).unwrap()));
+            Ok(())
+        })
+        .unwrap();
+    rows
+}
+
+#[test]
+fn lists_commits() {
+    let reader = Reader::open_mmap(fixture("history.db")).unwrap();
+    let wal = reader.wal().unwrap();
+    let commits: Vec<_> = wal
+        .commits()
+        .iter()
+        .map(|commit| (commit.number, commit.frames.len(), commit.pages.clone()))
+        .collect();
+    assert_eq!(
+        commits,
+        [(1, 2, vec![1, 2]), (2, 1, vec![2]), (3, 1, vec![2])]
+    );
+    assert_eq!(wal.commit(), 3);
+}
+
+#[test]
+fn reads_the_database_as_of_each_commit() {
+    let names = |rows: Vec<(i64, String)>| -> Vec<String> {
+        rows.into_iter().map(|(_, name)| name).collect()
+    };
+    assert_eq!(names(names_at(1)), ["one", "two", "three", "four", "five"]);
+    assert_eq!(names(names_at(2)), ["one", "TWO", "three", "four", "five"]);
+    assert_eq!(names(names_at(3)), ["one", "TWO", "four", "five", "six"]);
+
+    let before_wal = Reader::open_mmap(fixture("history.db"))
+        .unwrap()
+        .at_commit(0)
+        .unwrap();
+    assert!(before_wal.get_tables_map().unwrap().is_empty());
+}
+
+#[test]
+fn commit_past_the_last_one() {
+    let reader = Reader::open_mmap(fixture("history.db")).unwrap();
+    assert!(matches!(reader.at_commit(4), Err(SQLiteError::Other(_))));
+}
+
+#[test]
+fn diffs_a_table_between_commits() {
+    let older = Reader::open_mmap(fixture("history.db"))
+        .unwrap()
+        .at_commit(1)
+        .unwrap();
+    let newer = Reader::open_mmap(fixture("history.db")).unwrap();
+
+    let mut changes = Vec::new();
+    older
+        .diff_table(&newer, "t", |change, old_row, new_row| {
+            let name = |row: Option<&TableRow<'_>>| {
+                row.map(|row| text(&row.values().unwrap()[1]).unwrap())
+            };
+            changes.push((change, name(old_row), name(new_row)));
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        changes,
+        [
+            (RowChange::Updated, Some("two".into()), Some("TWO".into())),
+            (RowChange::Deleted, Some("three".into()), None),
+            (RowChange::Inserted, None, Some("six".into())),
+        ]
+    );
+
+    // from nothing at all, every row is inserted
+    let empty = Reader::open_mmap(fixture("history.db"))
+        .unwrap()
+        .at_commit(0)
+        .unwrap();
+    let mut inserted = 0;
+    empty
+        .diff_table(&newer, "t", |change, _, _| {
+            assert_eq!(change, RowChange::Inserted);
+            inserted += 1;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(inserted, 5);
+}
+
+#[test]
+fn torn_last_commit_is_dropped() {
+    let database = read_fixture("history.db");
+    let mut wal = read_fixture("history.db-wal");
+    wal.truncate(wal.len() - 100);
+
+    let wal = Wal::from_source(wal).unwrap();
+    assert_eq!(wal.commits().len(), 2);
+    let reader = Reader::from_source(database)
+        .unwrap()
+        .with_wal(wal)
+        .unwrap();
+    let mut rows = 0;
+    reader
+        .stream_table_rows_sequential("t", |_, _| {
+            rows += 1;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(rows, 5);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::{page_mut, read_fixture, text};
+use sqlite3_dump::error::{CorruptionKind, SQLiteError};
+use sqlite3_dump::model::Payload;
+use sqlite3_dump::Reader;
+
+const PAGE_SIZE: usize = 512;
+/// root page of kv, an interior page over its leaves
+const KV_ROOT: u32 = 2;
+
+fn integer(value: &Option<Payload<'_>>) -> i64 {
+    match value {
+        Some(Payload::I64(n)) => *n,
+        other => panic!("expected an integer, got {other:?}"),
+    }
+}
+
+fn kv_rows(database: Vec<u8>) -> Result<Vec<(Option<String>, i64)>, SQLiteError> {
+    let reader = Reader::from_source(database)?;
+    let mut rows = Vec::new();
+    reader.stream_without_rowid_rows("kv", |values| {
+        rows.push((text(&values[0]), integer(&values[1])));
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+#[test]
+fn rows_in_primary_key_order() {
+    let expected: Vec<_> = (0..300).map(|i| (Some(format!("key {i:03}")), i)).collect();
+    assert_eq!(kv_rows(read_fixture("without_rowid.db")).unwrap(), expected);
+}
+
+#[test]
+fn values_in_declared_column_order() {
+    let reader = Reader::from_source(read_fixture("without_rowid.db")).unwrap();
+    let mut rows = Vec::new();
+    reader
+        .stream_without_rowid_rows("edges", |values| {
+            let weight = match values[2] {
+                Some(Payload::F64(weight)) => weight,
+                Some(Payload::I64(weight)) => weight as f64,
+                ref other => panic!("expected a number, got {other:?}"),
+            };
+            rows.push((integer(&values[0]), integer(&values[1]), weight));
+            Ok(())
+        })
+        .unwrap();
+
+    let mut expected: Vec<_> = (0..50).map(|i| (i % 7, i / 7, i as f64 / 2.0)).collect();
+    expected.sort_by_key(|&(dst, src, _)| (src, dst));
+    assert_eq!(rows, expected);
+}
+
+#[test]
+fn not_a_without_rowid_table() {
+    let reader = Reader::from_source(read_fixture("people.db")).unwrap();
+    assert!(reader
+        .stream_without_rowid_rows("people", |_| Ok(()))
+        .is_err());
+
+    let reader = Reader::from_source(read_fixture("without_rowid.db")).unwrap();
+    assert!(reader
+        .stream_table_rows_sequential("kv", |_, _| Ok(()))
+        .is_err());
+}
+
+#[test]
+fn table_page_in_a_without_rowid_tree() {
+    let mut database = read_fixture("without_rowid.db");
+    page_mut(&mut database, PAGE_SIZE, KV_ROOT)[0] = 0x05;
+
+    match kv_rows(database) {
+        Err(SQLiteError::Corrupt { page, kind, .. }) => {
+            assert_eq!(page, KV_ROOT);
+            assert_eq!(kind, CorruptionKind::InvalidPageType(0x05));
+        }
+        other => panic!("expected an invalid page type, got {other:?}"),
+    }
+}